@@ -0,0 +1,24 @@
+// cargo run --example sdl --features sdl2 -- path/to/rom.ch8 [scale]
+use chip_8::backends::sdl;
+use chip_8::{Chip8VM, Chip8VMOptions};
+
+fn main() -> Result<(), String> {
+    let mut args = std::env::args().skip(1);
+    let rom = args.next().ok_or("usage: sdl <rom.ch8> [scale]")?;
+    let scale = match args.next() {
+        Some(s) => s.parse().map_err(|_| format!("invalid scale '{s}'"))?,
+        None => 10,
+    };
+
+    let mut vm = Chip8VM::new(
+        None,
+        None,
+        Some(Chip8VMOptions {
+            hide_display: true,
+            manual_timers: true,
+            ..Default::default()
+        }),
+    );
+    vm.load_rom_from_file(&rom);
+    sdl::run(&mut vm, scale)
+}
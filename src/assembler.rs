@@ -0,0 +1,324 @@
+use alloc::collections::BTreeMap;
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+
+use crate::Chip8VM;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AsmError {
+    pub line: usize,
+    pub message: String,
+}
+impl core::fmt::Display for AsmError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+#[cfg(feature = "std")]
+impl std::error::Error for AsmError {}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Operand {
+    Reg(u8),
+    Value(u16),
+    Label(String),
+    I,
+    IndirectI,
+    Delay,
+    Sound,
+    Key,
+    Font,
+    BigFont,
+    Bcd,
+}
+
+struct Statement<'a> {
+    line: usize,
+    mnemonic: String,
+    operands: Vec<&'a str>,
+}
+
+// Assembles a program written with the usual CHIP-8 mnemonics (CLS, LD, DRW, ...).
+// Labels are declared as `name:` and resolved assuming the program is loaded at 0x200.
+// Comments start with `;`. `DB` and `DW` emit raw bytes and words.
+pub fn assemble(source: &str) -> Result<Vec<u8>, AsmError> {
+    let mut labels: BTreeMap<String, u16> = BTreeMap::new();
+    let mut statements = Vec::new();
+    let mut addr = Chip8VM::RAM_ROM_START;
+
+    //First pass: collect labels and compute addresses
+    for (idx, raw) in source.lines().enumerate() {
+        let line = idx + 1;
+        let mut text = raw.split(';').next().unwrap_or("").trim();
+
+        while let Some(colon) = text.find(':') {
+            let label = text[..colon].trim();
+            if !is_identifier(label) {
+                return Err(error(line, format!("invalid label '{label}'")));
+            }
+            if labels
+                .insert(label.to_ascii_lowercase(), addr as u16)
+                .is_some()
+            {
+                return Err(error(line, format!("duplicate label '{label}'")));
+            }
+            text = text[colon + 1..].trim();
+        }
+        if text.is_empty() {
+            continue;
+        }
+
+        let (mnemonic, rest) = text.split_once(char::is_whitespace).unwrap_or((text, ""));
+        let operands: Vec<&str> = if rest.trim().is_empty() {
+            Vec::new()
+        } else {
+            rest.split(',').map(str::trim).collect()
+        };
+        let mnemonic = mnemonic.to_ascii_uppercase();
+        addr += match mnemonic.as_str() {
+            "DB" => operands.len(),
+            "DW" => 2 * operands.len(),
+            _ => 2,
+        };
+        if addr > Chip8VM::RAM_SIZE {
+            return Err(error(line, "program does not fit in RAM".to_string()));
+        }
+        statements.push(Statement {
+            line,
+            mnemonic,
+            operands,
+        });
+    }
+
+    //Second pass: encode with every label known
+    let mut rom = Vec::new();
+    for stmt in &statements {
+        let ops = stmt
+            .operands
+            .iter()
+            .map(|op| parse_operand(op).map_err(|msg| error(stmt.line, msg)))
+            .collect::<Result<Vec<_>, _>>()?;
+        let ctx = Encoder {
+            line: stmt.line,
+            labels: &labels,
+        };
+        match stmt.mnemonic.as_str() {
+            "DB" => {
+                for op in &ops {
+                    rom.push(ctx.value(op, 0xFF)? as u8);
+                }
+            }
+            "DW" => {
+                for op in &ops {
+                    rom.extend_from_slice(&ctx.value(op, 0xFFFF)?.to_be_bytes());
+                }
+            }
+            m => rom.extend_from_slice(&ctx.encode(m, &ops)?.to_be_bytes()),
+        }
+    }
+    Ok(rom)
+}
+
+struct Encoder<'a> {
+    line: usize,
+    labels: &'a BTreeMap<String, u16>,
+}
+impl Encoder<'_> {
+    fn encode(&self, mnemonic: &str, ops: &[Operand]) -> Result<u16, AsmError> {
+        use Operand::*;
+        let op = match (mnemonic, ops) {
+            ("SYS", [a]) => self.addr(a)?,
+            ("CLS", []) => 0x00E0,
+            ("RET", []) => 0x00EE,
+            ("JP", [Reg(0), a]) => 0xB000 | self.addr(a)?,
+            ("JP", [a]) => 0x1000 | self.addr(a)?,
+            ("CALL", [a]) => 0x2000 | self.addr(a)?,
+            ("SE", [Reg(x), Reg(y)]) => xy(0x5000, *x, *y),
+            ("SE", [Reg(x), v]) => xnn(0x3000, *x, self.byte(v)?),
+            ("SNE", [Reg(x), Reg(y)]) => xy(0x9000, *x, *y),
+            ("SNE", [Reg(x), v]) => xnn(0x4000, *x, self.byte(v)?),
+            ("LD", [I, a]) => 0xA000 | self.addr(a)?,
+            ("LD", [Reg(x), Reg(y)]) => xy(0x8000, *x, *y),
+            ("LD", [Reg(x), Delay]) => xnn(0xF000, *x, 0x07),
+            ("LD", [Reg(x), Key]) => xnn(0xF000, *x, 0x0A),
+            ("LD", [Delay, Reg(x)]) => xnn(0xF000, *x, 0x15),
+            ("LD", [Sound, Reg(x)]) => xnn(0xF000, *x, 0x18),
+            ("LD", [Font, Reg(x)]) => xnn(0xF000, *x, 0x29),
+            ("LD", [BigFont, Reg(x)]) => xnn(0xF000, *x, 0x30),
+            ("LD", [Bcd, Reg(x)]) => xnn(0xF000, *x, 0x33),
+            ("LD", [IndirectI, Reg(x)]) => xnn(0xF000, *x, 0x55),
+            ("LD", [Reg(x), IndirectI]) => xnn(0xF000, *x, 0x65),
+            ("LD", [Reg(x), v]) => xnn(0x6000, *x, self.byte(v)?),
+            ("ADD", [I, Reg(x)]) => xnn(0xF000, *x, 0x1E),
+            ("ADD", [Reg(x), Reg(y)]) => xy(0x8004, *x, *y),
+            ("ADD", [Reg(x), v]) => xnn(0x7000, *x, self.byte(v)?),
+            ("OR", [Reg(x), Reg(y)]) => xy(0x8001, *x, *y),
+            ("AND", [Reg(x), Reg(y)]) => xy(0x8002, *x, *y),
+            ("XOR", [Reg(x), Reg(y)]) => xy(0x8003, *x, *y),
+            ("SUB", [Reg(x), Reg(y)]) => xy(0x8005, *x, *y),
+            ("SHR", [Reg(x)]) => xy(0x8006, *x, *x),
+            ("SHR", [Reg(x), Reg(y)]) => xy(0x8006, *x, *y),
+            ("SUBN", [Reg(x), Reg(y)]) => xy(0x8007, *x, *y),
+            ("SHL", [Reg(x)]) => xy(0x800E, *x, *x),
+            ("SHL", [Reg(x), Reg(y)]) => xy(0x800E, *x, *y),
+            ("RND", [Reg(x), v]) => xnn(0xC000, *x, self.byte(v)?),
+            ("DRW", [Reg(x), Reg(y), n]) => xy(0xD000, *x, *y) | self.value(n, 0xF)?,
+            ("SKP", [Reg(x)]) => xnn(0xE000, *x, 0x9E),
+            ("SKNP", [Reg(x)]) => xnn(0xE000, *x, 0xA1),
+            _ => {
+                return Err(error(
+                    self.line,
+                    format!(
+                        "invalid instruction '{mnemonic}' with {} operand(s)",
+                        ops.len()
+                    ),
+                ))
+            }
+        };
+        Ok(op)
+    }
+
+    fn addr(&self, op: &Operand) -> Result<u16, AsmError> {
+        self.value(op, 0xFFF)
+    }
+
+    fn byte(&self, op: &Operand) -> Result<u8, AsmError> {
+        Ok(self.value(op, 0xFF)? as u8)
+    }
+
+    fn value(&self, op: &Operand, max: u16) -> Result<u16, AsmError> {
+        let v = match op {
+            Operand::Value(v) => *v,
+            Operand::Label(name) => *self
+                .labels
+                .get(&name.to_ascii_lowercase())
+                .ok_or_else(|| error(self.line, format!("unknown label '{name}'")))?,
+            other => return Err(error(self.line, format!("expected a value, got {other:?}"))),
+        };
+        if v > max {
+            return Err(error(
+                self.line,
+                format!("value {v:#x} larger than {max:#x}"),
+            ));
+        }
+        Ok(v)
+    }
+}
+
+fn xy(base: u16, x: u8, y: u8) -> u16 {
+    base | (x as u16) << 8 | (y as u16) << 4
+}
+
+fn xnn(base: u16, x: u8, nn: u8) -> u16 {
+    base | (x as u16) << 8 | nn as u16
+}
+
+fn parse_operand(op: &str) -> Result<Operand, String> {
+    let upper = op.to_ascii_uppercase();
+    let operand = match upper.as_str() {
+        "" => return Err("missing operand".to_string()),
+        "I" => Operand::I,
+        "[I]" => Operand::IndirectI,
+        "DT" => Operand::Delay,
+        "ST" => Operand::Sound,
+        "K" => Operand::Key,
+        "F" => Operand::Font,
+        "HF" => Operand::BigFont,
+        "B" => Operand::Bcd,
+        r if r.len() == 2 && r.starts_with('V') => u8::from_str_radix(&r[1..], 16)
+            .map(Operand::Reg)
+            .map_err(|_| format!("invalid register '{op}'"))?,
+        _ => {
+            let parsed = if let Some(hex) = upper.strip_prefix("0X").or(upper.strip_prefix('#')) {
+                u16::from_str_radix(hex, 16)
+            } else if let Some(bin) = upper.strip_prefix("0B") {
+                u16::from_str_radix(bin, 2)
+            } else if upper.starts_with(|c: char| c.is_ascii_digit()) {
+                upper.parse()
+            } else if is_identifier(op) {
+                return Ok(Operand::Label(op.to_string()));
+            } else {
+                return Err(format!("invalid operand '{op}'"));
+            };
+            Operand::Value(parsed.map_err(|_| format!("invalid number '{op}'"))?)
+        }
+    };
+    Ok(operand)
+}
+
+fn is_identifier(s: &str) -> bool {
+    s.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && s.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn error(line: usize, message: String) -> AsmError {
+    AsmError { line, message }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::test_vm;
+    use alloc::vec;
+
+    #[test]
+    fn assemble_basic() {
+        let rom = assemble(
+            "
+            CLS
+            LD V0, 0x0C   ; x
+            LD V1, 8
+            LD I, 0x50
+            DRW V0, V1, 5
+            ",
+        )
+        .unwrap();
+        assert_eq!(
+            rom,
+            vec![0x00, 0xE0, 0x60, 0x0C, 0x61, 0x08, 0xA0, 0x50, 0xD0, 0x15]
+        );
+    }
+
+    #[test]
+    fn assemble_labels() {
+        let rom = assemble(
+            "
+            start: JP end
+            sprite: DB 0xFF, 0b10000001
+            end:
+                LD I, sprite
+                CALL start
+            SYS sprite
+            ",
+        )
+        .unwrap();
+        assert_eq!(
+            rom,
+            vec![0x12, 0x04, 0xFF, 0x81, 0xA2, 0x02, 0x22, 0x00, 0x02, 0x02]
+        );
+    }
+
+    #[test]
+    fn assemble_errors() {
+        let err = assemble("CLS\nJP nowhere").unwrap_err();
+        assert_eq!(err.line, 2);
+        assert_eq!(assemble("LD V0, 0x100").unwrap_err().line, 1);
+        assert_eq!(assemble("\n\nFOO V0").unwrap_err().line, 3);
+        assert_eq!(assemble("a:\na:").unwrap_err().line, 2);
+    }
+
+    #[test]
+    fn assemble_runs() {
+        let rom = assemble("LD V3, 42\nADD V3, 1\nLD B, V3\nLD HF, V3").unwrap();
+        let mut vm = test_vm();
+        vm.load_rom(&rom);
+        for _ in 0..4 {
+            vm.run_once().unwrap();
+        }
+        assert_eq!(vm.registers.get(3), 43);
+        assert_eq!(vm.registers.i, vm.big_char_index(43));
+    }
+}
@@ -0,0 +1,134 @@
+use ::minifb::{Key, KeyRepeat, Window, WindowOptions};
+
+use crate::{Chip8VM, Display, Intensity, Keypad, Pacer, Renderer, Timers};
+
+const ON: u32 = 0x00FF_FFFF;
+const OFF: u32 = 0x0000_0000;
+
+// Keeps a one pixel per CHIP-8 pixel buffer, minifb stretches it to the window size
+pub struct MinifbRenderer {
+    window: Window,
+    buffer: Vec<u32>,
+}
+impl MinifbRenderer {
+    pub fn new(scale: usize) -> Result<Self, String> {
+        let mut window = Window::new(
+            "CHIP-8",
+            Chip8VM::DISPLAY_WIDTH * scale,
+            Chip8VM::DISPLAY_HEIGHT * scale,
+            WindowOptions::default(),
+        )
+        .map_err(|e| e.to_string())?;
+        // Frames are paced by the caller
+        window.set_target_fps(0);
+        Ok(MinifbRenderer {
+            window,
+            buffer: vec![OFF; Chip8VM::DISPLAY_WIDTH * Chip8VM::DISPLAY_HEIGHT],
+        })
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.window.is_open() && !self.window.is_key_down(Key::Escape)
+    }
+
+    // Also pumps the window events, so keys are only updated by rendering
+    fn update(&mut self, width: usize, height: usize) {
+        let _ = self.window.update_with_buffer(&self.buffer, width, height);
+    }
+
+    // F5 went down since the last render
+    pub fn reload_pressed(&self) -> bool {
+        self.window.is_key_pressed(Key::F5, KeyRepeat::No)
+    }
+
+    // Keys currently held in the window, as of the last render
+    pub fn keypad(&self) -> Keypad {
+        let mut keypad = Keypad::default();
+        for key in self.window.get_keys() {
+            if let Some(k) = map_key(key) {
+                keypad.press(k);
+            }
+        }
+        keypad
+    }
+}
+impl Renderer for MinifbRenderer {
+    fn render(&mut self, display: &Display) {
+        let width = display[0].len();
+        for (y, row) in display.iter().enumerate() {
+            for (x, &on) in row.iter().enumerate() {
+                self.buffer[y * width + x] = if on { ON } else { OFF };
+            }
+        }
+        self.update(width, display.len());
+    }
+
+    fn render_intensity(&mut self, _display: &Display, intensity: &Intensity) {
+        let width = intensity[0].len();
+        for (y, row) in intensity.iter().enumerate() {
+            for (x, &level) in row.iter().enumerate() {
+                self.buffer[y * width + x] = level as u32 * 0x0001_0101;
+            }
+        }
+        self.update(width, intensity.len());
+    }
+}
+
+// Same layout as the SDL backend:
+//   1 2 3 4      1 2 3 C
+//   Q W E R  ->  4 5 6 D
+//   A S D F      7 8 9 E
+//   Z X C V      A 0 B F
+pub fn map_key(key: Key) -> Option<u8> {
+    let k = match key {
+        Key::Key1 => 0x1,
+        Key::Key2 => 0x2,
+        Key::Key3 => 0x3,
+        Key::Key4 => 0xC,
+        Key::Q => 0x4,
+        Key::W => 0x5,
+        Key::E => 0x6,
+        Key::R => 0xD,
+        Key::A => 0x7,
+        Key::S => 0x8,
+        Key::D => 0x9,
+        Key::F => 0xE,
+        Key::Z => 0xA,
+        Key::X => 0x0,
+        Key::C => 0xB,
+        Key::V => 0xF,
+        _ => return None,
+    };
+    Some(k)
+}
+
+// Opens a window of the display size times `scale` and runs the VM at `vm.freq`
+// until the window is closed or Escape is pressed. F5 reloads the ROM file, see
+// `Chip8VM::reload_rom_from_file`.
+pub fn run(vm: &mut Chip8VM, scale: usize) -> Result<(), String> {
+    let mut renderer = MinifbRenderer::new(scale)?;
+
+    vm.pre_run();
+    let mut pacer = Pacer::new();
+    // Fraction of instruction left over from the previous frames
+    let mut budget = 0.;
+    while renderer.is_open() {
+        if renderer.reload_pressed() {
+            if let Err(e) = vm.reload_rom_from_file() {
+                vm.warnln(&format!("cannot reload the ROM: {e}"));
+            }
+        }
+        vm.set_keypad_state(renderer.keypad().bits());
+
+        budget += vm.instructions_per_frame();
+        while budget >= 1. {
+            vm.run_once().map_err(|e| e.to_string())?;
+            budget -= 1.;
+        }
+        vm.tick_timers();
+        vm.present();
+        renderer.render_intensity(vm.front_display(), vm.intensity());
+        pacer.wait(Timers::TIMER_FREQ);
+    }
+    Ok(())
+}
@@ -0,0 +1,5 @@
+// Windowed frontends, each behind its own feature
+#[cfg(feature = "minifb")]
+pub mod minifb;
+#[cfg(feature = "sdl2")]
+pub mod sdl;
@@ -0,0 +1,151 @@
+use sdl2::event::Event;
+use sdl2::keyboard::Keycode;
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use sdl2::render::Canvas;
+use sdl2::video::Window;
+
+use crate::{Chip8VM, Display, Intensity, Pacer, Renderer, Timers};
+
+pub struct SdlRenderer {
+    canvas: Canvas<Window>,
+    scale: u32,
+}
+impl SdlRenderer {
+    pub fn new(canvas: Canvas<Window>, scale: u32) -> Self {
+        SdlRenderer { canvas, scale }
+    }
+}
+impl Renderer for SdlRenderer {
+    fn render(&mut self, display: &Display) {
+        self.canvas.set_draw_color(Color::BLACK);
+        self.canvas.clear();
+        self.canvas.set_draw_color(Color::WHITE);
+        for (y, row) in display.iter().enumerate() {
+            for (x, _) in row.iter().enumerate().filter(|(_, &on)| on) {
+                let _ = self.canvas.fill_rect(Rect::new(
+                    x as i32 * self.scale as i32,
+                    y as i32 * self.scale as i32,
+                    self.scale,
+                    self.scale,
+                ));
+            }
+        }
+        self.canvas.present();
+    }
+
+    fn render_intensity(&mut self, _display: &Display, intensity: &Intensity) {
+        self.canvas.set_draw_color(Color::BLACK);
+        self.canvas.clear();
+        for (y, row) in intensity.iter().enumerate() {
+            for (x, &level) in row.iter().enumerate().filter(|(_, &level)| level > 0) {
+                self.canvas.set_draw_color(Color::RGB(level, level, level));
+                let _ = self.canvas.fill_rect(Rect::new(
+                    x as i32 * self.scale as i32,
+                    y as i32 * self.scale as i32,
+                    self.scale,
+                    self.scale,
+                ));
+            }
+        }
+        self.canvas.present();
+    }
+}
+
+// Usual mapping of the COSMAC VIP hex keypad onto a QWERTY keyboard:
+//   1 2 3 4      1 2 3 C
+//   Q W E R  ->  4 5 6 D
+//   A S D F      7 8 9 E
+//   Z X C V      A 0 B F
+pub fn map_key(key: Keycode) -> Option<u8> {
+    let k = match key {
+        Keycode::Num1 => 0x1,
+        Keycode::Num2 => 0x2,
+        Keycode::Num3 => 0x3,
+        Keycode::Num4 => 0xC,
+        Keycode::Q => 0x4,
+        Keycode::W => 0x5,
+        Keycode::E => 0x6,
+        Keycode::R => 0xD,
+        Keycode::A => 0x7,
+        Keycode::S => 0x8,
+        Keycode::D => 0x9,
+        Keycode::F => 0xE,
+        Keycode::Z => 0xA,
+        Keycode::X => 0x0,
+        Keycode::C => 0xB,
+        Keycode::V => 0xF,
+        _ => return None,
+    };
+    Some(k)
+}
+
+// Opens a window of the display size times `scale` and runs the VM at `vm.freq`
+// until the window is closed or Escape is pressed. F5 reloads the ROM file, see
+// `Chip8VM::reload_rom_from_file`.
+pub fn run(vm: &mut Chip8VM, scale: u32) -> Result<(), String> {
+    let sdl = sdl2::init()?;
+    let window = sdl
+        .video()?
+        .window(
+            "CHIP-8",
+            Chip8VM::DISPLAY_WIDTH as u32 * scale,
+            Chip8VM::DISPLAY_HEIGHT as u32 * scale,
+        )
+        .position_centered()
+        .build()
+        .map_err(|e| e.to_string())?;
+    let canvas = window.into_canvas().build().map_err(|e| e.to_string())?;
+    let mut renderer = SdlRenderer::new(canvas, scale);
+    let mut events = sdl.event_pump()?;
+
+    vm.pre_run();
+    let mut pacer = Pacer::new();
+    // Fraction of instruction left over from the previous frames
+    let mut budget = 0.;
+    loop {
+        for event in events.poll_iter() {
+            match event {
+                Event::Quit { .. }
+                | Event::KeyDown {
+                    keycode: Some(Keycode::Escape),
+                    ..
+                } => return Ok(()),
+                Event::KeyDown {
+                    keycode: Some(Keycode::F5),
+                    repeat: false,
+                    ..
+                } => {
+                    if let Err(e) = vm.reload_rom_from_file() {
+                        vm.warnln(&format!("cannot reload the ROM: {e}"));
+                    }
+                }
+                Event::KeyDown {
+                    keycode: Some(k), ..
+                } => {
+                    if let Some(key) = map_key(k) {
+                        vm.keypad_mut().press(key);
+                    }
+                }
+                Event::KeyUp {
+                    keycode: Some(k), ..
+                } => {
+                    if let Some(key) = map_key(k) {
+                        vm.keypad_mut().release(key);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        budget += vm.instructions_per_frame();
+        while budget >= 1. {
+            vm.run_once().map_err(|e| e.to_string())?;
+            budget -= 1.;
+        }
+        vm.tick_timers();
+        vm.present();
+        renderer.render_intensity(vm.front_display(), vm.intensity());
+        pacer.wait(Timers::TIMER_FREQ);
+    }
+}
@@ -0,0 +1,82 @@
+use std::time::{Duration, Instant};
+
+use crate::{Chip8Error, Chip8VM, TimersHandle};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BenchResult {
+    pub instructions: u64,
+    pub elapsed: Duration,
+    // Instructions per second, the highest sustainable `freq` on this machine
+    pub hz: f64,
+    // 60Hz frames where the display was presented
+    pub frames: u64,
+}
+
+impl Chip8VM {
+    // Instructions run between two checks of the clock
+    const BENCH_BATCH: u32 = 1000;
+
+    // Runs the loaded ROM as fast as possible for about `duration`.
+    // The timers tick and the display is presented every `freq / 60` instructions, as if
+    // running at `freq`, so the measure includes the rendering cost. The timers are driven
+    // manually during the benchmark, the executed instructions are the same on every run.
+    pub fn benchmark(&mut self, duration: Duration) -> Result<BenchResult, Chip8Error> {
+        let manual = TimersHandle::Manual(self.timers.get());
+        let timers = core::mem::replace(&mut self.timers, manual);
+        let result = self.bench_loop(duration);
+        let values = self.timers.get();
+        self.timers = timers;
+        self.timers.modify(|t| *t = values);
+        result
+    }
+
+    fn bench_loop(&mut self, duration: Duration) -> Result<BenchResult, Chip8Error> {
+        let per_frame = (self.instructions_per_frame().round() as u64).max(1);
+        let start = Instant::now();
+        let mut instructions = 0;
+        let mut frames = 0;
+        while start.elapsed() < duration {
+            for _ in 0..Self::BENCH_BATCH {
+                self.run_once()?;
+                instructions += 1;
+                if instructions % per_frame == 0 {
+                    self.tick_timers();
+                    if self.display_changed || self.fading {
+                        self.present_frame();
+                        frames += 1;
+                    }
+                }
+            }
+        }
+        let elapsed = start.elapsed();
+        Ok(BenchResult {
+            instructions,
+            elapsed,
+            hz: instructions as f64 / elapsed.as_secs_f64(),
+            frames,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::test_vm;
+
+    #[test]
+    fn benchmark_ibm() {
+        // Manual timers, so nothing ticks them behind the benchmark's back
+        let mut vm = test_vm();
+        vm.load_rom(include_bytes!("../ibm.ch8"));
+        vm.timers.modify(|t| t.delay = 200);
+        let result = vm.benchmark(Duration::from_millis(50)).unwrap();
+        assert!(result.elapsed >= Duration::from_millis(50));
+        assert_eq!(result.instructions, vm.cycles());
+        assert!(result.instructions >= Chip8VM::BENCH_BATCH as u64);
+        assert!(result.hz > 0.);
+        assert!(result.frames >= 1);
+        // Ticked once per 12 instructions at the default 700Hz, then copied back
+        let ticks = (result.instructions / 12).min(200) as u8;
+        assert_eq!(vm.timers.get().delay, 200 - ticks);
+    }
+}
@@ -0,0 +1,221 @@
+use std::sync::mpsc::{Receiver, SyncSender, TryRecvError, TrySendError};
+use std::time::Instant;
+
+use crate::{Chip8Error, Chip8VM, Display, Pacer, Timers};
+
+// Commands from the UI thread, see `Chip8VM::run_with_channels`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InputEvent {
+    KeyDown(u8),
+    KeyUp(u8),
+    Pause,
+    Resume,
+    // See `Chip8VM::set_turbo`
+    Turbo(bool),
+    // Makes `run_with_channels` return
+    Stop,
+    // Restarts the VM on this ROM (RAM after the ROM start is cleared). A ROM too big
+    // for the RAM makes `run_with_channels` return `Chip8Error::RomTooBig`, untouched.
+    Load(Vec<u8>),
+}
+
+// Output sent to the UI thread
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FrameEvent {
+    // A newly presented display, only sent when it changed
+    Frame(Box<Display>),
+    // The buzzer started or stopped
+    Buzzer(bool),
+}
+
+impl Chip8VM {
+    // Runs the VM on the calling thread, driven by `input` and reporting to `output`,
+    // until `InputEvent::Stop` or until either side of the channels hangs up.
+    // Input events are applied at the start of each 60Hz frame.
+    //
+    // Back-pressure: the VM never blocks on `output`. When it is full, the display and
+    // buzzer are sent again on the following frames until there is room (a pending
+    // frame is replaced by the latest display), so the UI always ends up with
+    // the latest state.
+    pub fn run_with_channels(
+        &mut self,
+        input: Receiver<InputEvent>,
+        output: SyncSender<FrameEvent>,
+    ) -> Result<(), Chip8Error> {
+        self.pre_run();
+        let mut pacer = Pacer::new();
+        // Fraction of instruction left over from the previous frames
+        let mut budget = 0.;
+        let mut buzzer_sent = false;
+        let mut frame_pending = false;
+        loop {
+            loop {
+                match input.try_recv() {
+                    Ok(InputEvent::KeyDown(key)) => self.set_key(key, true)?,
+                    Ok(InputEvent::KeyUp(key)) => self.set_key(key, false)?,
+                    Ok(InputEvent::Pause) => self.pause(),
+                    Ok(InputEvent::Resume) => self.resume(),
+                    Ok(InputEvent::Turbo(turbo)) => self.set_turbo(turbo),
+                    Ok(InputEvent::Stop) | Err(TryRecvError::Disconnected) => return Ok(()),
+                    Ok(InputEvent::Load(rom)) => {
+                        self.restart(&rom)?;
+                    }
+                    Err(TryRecvError::Empty) => break,
+                }
+            }
+
+            if !self.paused && self.turbo {
+                // As many instructions as fit in the frame
+                budget = 0.;
+                let end = pacer.next(Timers::TIMER_FREQ);
+                while Instant::now() < end {
+                    self.run_once()?;
+                }
+            } else if !self.paused {
+                budget += self.instructions_per_frame();
+                while budget >= 1. {
+                    self.run_once()?;
+                    budget -= 1.;
+                }
+            }
+            self.tick_timers();
+            if self.display_changed || self.fading {
+                self.present_frame();
+                frame_pending = true;
+            }
+
+            if frame_pending {
+                match output.try_send(FrameEvent::Frame(Box::new(self.front))) {
+                    Ok(()) => frame_pending = false,
+                    Err(TrySendError::Full(_)) => {}
+                    Err(TrySendError::Disconnected(_)) => return Ok(()),
+                }
+            }
+            let buzzer = self.is_buzzing();
+            if buzzer != buzzer_sent {
+                match output.try_send(FrameEvent::Buzzer(buzzer)) {
+                    Ok(()) => buzzer_sent = buzzer,
+                    Err(TrySendError::Full(_)) => {}
+                    Err(TrySendError::Disconnected(_)) => return Ok(()),
+                }
+            }
+            pacer.wait(Timers::TIMER_FREQ);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc::{channel, sync_channel};
+    use std::thread;
+    use std::time::Duration;
+
+    use super::*;
+    use crate::tests::test_vm;
+
+    #[test]
+    fn drive_from_another_thread() {
+        let (input, events) = channel();
+        let (frames, output) = sync_channel(4);
+        let worker = thread::spawn(move || {
+            let mut vm = test_vm();
+            vm.set_freq(2000).unwrap();
+            vm.run_with_channels(events, frames).map(|()| vm.cycles())
+        });
+
+        input
+            .send(InputEvent::Load(include_bytes!("../ibm.ch8").to_vec()))
+            .unwrap();
+        input.send(InputEvent::KeyDown(0x5)).unwrap();
+        // The IBM logo is complete after a few frames
+        let logo = crate::run_headless(include_bytes!("../ibm.ch8"), 100, 0).unwrap();
+        loop {
+            match output.recv_timeout(Duration::from_secs(1)).unwrap() {
+                FrameEvent::Frame(display) if *display == logo => break,
+                _ => {}
+            }
+        }
+
+        input.send(InputEvent::Stop).unwrap();
+        assert!(worker.join().unwrap().unwrap() > 0);
+    }
+
+    #[test]
+    fn turbo() {
+        let run = |turbo: bool| {
+            let (input, events) = channel();
+            let (frames, _output) = sync_channel(100);
+            let mut vm = test_vm();
+            vm.set_freq(60).unwrap();
+            vm.load_rom(&[0x12, 0x00]);
+            input.send(InputEvent::Turbo(turbo)).unwrap();
+            let worker = thread::spawn(move || {
+                vm.run_with_channels(events, frames).unwrap();
+                vm.cycles()
+            });
+            thread::sleep(Duration::from_millis(100));
+            input.send(InputEvent::Stop).unwrap();
+            worker.join().unwrap()
+        };
+        // One instruction per frame
+        assert!(run(false) <= 10);
+        assert!(run(true) > 1000);
+    }
+
+    #[test]
+    fn invalid_key_stops() {
+        let (input, events) = channel();
+        let (frames, _output) = sync_channel(1);
+        input.send(InputEvent::KeyDown(0x10)).unwrap();
+        let mut vm = test_vm();
+        assert_eq!(
+            vm.run_with_channels(events, frames),
+            Err(Chip8Error::InvalidKey(0x10))
+        );
+    }
+    #[test]
+    fn rom_too_big_stops() {
+        let (input, events) = channel();
+        let (frames, _output) = sync_channel(1);
+        let mut vm = test_vm();
+        vm.load_rom(&[0x12, 0x00]);
+        input.send(InputEvent::Load(vec![0xFF; 4000])).unwrap();
+        assert_eq!(
+            vm.run_with_channels(events, frames),
+            Err(Chip8Error::RomTooBig(4000, 0xE00))
+        );
+        assert_eq!(vm.read_mem_slice(0x200, 3), Some(&[0x12, 0x00, 0x00][..]));
+    }
+
+    #[test]
+    fn last_frame_after_full_channel() {
+        let (input, events) = channel();
+        let (frames, output) = sync_channel(1);
+        // LD F, V0 ; loop: DRW V1, V1, 5 ; ADD V1, 8 ; SE V1, 32 ; JP loop ; end: JP end
+        let rom = [
+            0xF0, 0x29, 0xD1, 0x15, 0x71, 0x08, 0x31, 0x20, 0x12, 0x02, 0x12, 0x0A,
+        ];
+        let worker = thread::spawn(move || {
+            // One instruction per frame, the sprites are drawn over several frames
+            let mut vm = test_vm();
+            vm.set_freq(60).unwrap();
+            vm.load_rom(&rom);
+            vm.run_with_channels(events, frames)
+        });
+
+        // Not drained until the drawing is over, the first frame fills the channel
+        thread::sleep(Duration::from_millis(500));
+        let FrameEvent::Frame(first) = output.recv().unwrap() else {
+            panic!("no frame");
+        };
+        let last = crate::run_headless(&rom, 100, 0).unwrap();
+        assert_ne!(*first, last);
+        match output.recv_timeout(Duration::from_secs(1)).unwrap() {
+            FrameEvent::Frame(display) => assert_eq!(*display, last),
+            event => panic!("{event:?}"),
+        }
+
+        input.send(InputEvent::Stop).unwrap();
+        worker.join().unwrap().unwrap();
+    }
+}
@@ -1,15 +1,131 @@
-use std::fmt::Debug;
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+pub mod assembler;
+#[cfg(feature = "std")]
+pub mod backends;
+#[cfg(feature = "std")]
+pub mod bench;
+#[cfg(feature = "std")]
+pub mod channels;
+#[cfg(feature = "std")]
+pub mod clock;
+pub mod disassembler;
+#[cfg(feature = "std")]
+pub mod export;
+#[cfg(feature = "gif")]
+pub mod gif;
+mod history;
+pub mod octo;
+#[cfg(feature = "std")]
+pub mod replay;
+mod state;
+#[cfg(feature = "std")]
+pub mod stats;
+#[cfg(feature = "std")]
+pub mod terminal;
+#[cfg(feature = "std")]
+pub mod trace;
+pub mod transform;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+
+pub use state::{RegisterPatch, VmState};
+
+use alloc::{boxed::Box, collections::BTreeSet, format, string::String, vec::Vec};
+#[cfg(feature = "std")]
+use clock::{Clock, SystemClock};
+use core::fmt::{Debug, Write as _};
+use core::time::Duration;
+use rand::{rngs::StdRng, Rng, RngCore, SeedableRng};
+#[cfg(feature = "std")]
+use std::collections::HashMap;
+#[cfg(feature = "std")]
 use std::io::Read;
+#[cfg(feature = "std")]
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+#[cfg(feature = "std")]
 use std::sync::{Arc, Condvar, Mutex};
-use std::{thread, time::Duration, time::Instant};
+#[cfg(feature = "std")]
+use std::{thread, time::Instant};
+#[cfg(feature = "std")]
+use terminal::{TerminalBorder, TerminalInput, TerminalRenderer};
 
 type Ram = [u8; Chip8VM::RAM_SIZE];
 type Font = [u8; Chip8VM::FONT_SIZE];
-type Display = [[bool; Chip8VM::DISPLAY_WIDTH]; Chip8VM::DISPLAY_HEIGHT];
+type BigFont = [u8; Chip8VM::BIG_FONT_SIZE];
+pub type Display = [[bool; Chip8VM::DISPLAY_WIDTH]; Chip8VM::DISPLAY_HEIGHT];
+// Brightness of each pixel, 255 when on and fading to 0 once off (see `phosphor_frames`)
+pub type Intensity = [[u8; Chip8VM::DISPLAY_WIDTH]; Chip8VM::DISPLAY_HEIGHT];
 type U4 = u8;
 type U12 = u16;
 
-#[derive(Default)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Chip8Error {
+    // Access to an address outside of the RAM
+    OutOfBounds(usize),
+    // A saved state that cannot be loaded, with the offending part
+    InvalidState(&'static str),
+    // Key number above 0xF
+    InvalidKey(u8),
+    // PC or I moved past 0xFFF, without the `wrap_addresses` option
+    AddressOverflow(usize),
+    // Write to a protected part of the RAM (the fonts, or below 0x200 with
+    // `protect_reserved`), at this address
+    WriteProtected(usize),
+    // Clock speed outside of `Chip8VM::MIN_FREQ..=Chip8VM::MAX_FREQ`
+    InvalidFrequency(u32),
+    // ROM size and space available from the load address
+    RomTooBig(usize, usize),
+    // Font with an empty glyph, or wider than 4 pixels for the small font, for this digit
+    InvalidFont(u8),
+    // Machine invariant checked by the `strict` option, with the offending value
+    BrokenInvariant(&'static str, usize),
+    // Opcode that isn't an instruction, without the `skip_unknown` option
+    UnknownOpcode(u16),
+    // Return (00EE) without a call, at this address
+    StackUnderflow(usize),
+    // The instruction at this address, with this opcode, panicked (only returned with
+    // the `catch_panics` feature)
+    InternalPanic(usize, u16),
+}
+impl core::fmt::Display for Chip8Error {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::OutOfBounds(addr) => write!(f, "address {addr:#x} is out of RAM bounds"),
+            Self::InvalidState(what) => write!(f, "invalid state: {what}"),
+            Self::InvalidKey(key) => write!(f, "invalid key {key:#x}, keys go from 0 to 0xF"),
+            Self::AddressOverflow(addr) => {
+                write!(f, "address {addr:#x} overflows the 12-bit address space")
+            }
+            Self::WriteProtected(addr) => write!(f, "address {addr:#x} is write protected"),
+            Self::InvalidFrequency(freq) => write!(
+                f,
+                "invalid frequency {freq}Hz, it must be between {}Hz and {}Hz",
+                Chip8VM::MIN_FREQ,
+                Chip8VM::MAX_FREQ
+            ),
+            Self::RomTooBig(size, available) => {
+                write!(f, "ROM too big: {size}B for {available}B available")
+            }
+            Self::InvalidFont(digit) => write!(f, "invalid font glyph for digit {digit:#x}"),
+            Self::BrokenInvariant(what, value) => {
+                write!(f, "broken invariant: {what} (got {value:#x})")
+            }
+            Self::UnknownOpcode(opcode) => write!(f, "unknown opcode {opcode:#06x}"),
+            Self::StackUnderflow(addr) => write!(f, "return without a call at {addr:#x}"),
+            Self::InternalPanic(addr, opcode) => write!(
+                f,
+                "internal error running opcode {opcode:#06x} at {addr:#x}"
+            ),
+        }
+    }
+}
+#[cfg(feature = "std")]
+impl std::error::Error for Chip8Error {}
+
+#[derive(Default, Clone, Copy)]
 struct Registers {
     pc: U12,
     i: U12,
@@ -82,8 +198,8 @@ impl Registers {
         }
     }
 }
-impl std::fmt::Debug for Registers {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+impl core::fmt::Debug for Registers {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         writeln!(f, "---  Registers  ---")?;
         write!(f, " pc|")?;
         writeln!(f, "  i|")?;
@@ -112,7 +228,7 @@ impl std::fmt::Debug for Registers {
     }
 }
 
-#[derive(Default, Debug)]
+#[derive(Default, Debug, Clone, Copy)]
 struct Timers {
     delay: u8,
     buzzer: u8,
@@ -126,71 +242,400 @@ impl Timers {
     }
 }
 
+// State of the 16 keys, one bit per key
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Keypad(u16);
+impl Keypad {
+    pub fn press(&mut self, key: U4) {
+        if key < 16 {
+            self.0 |= 1 << key;
+        }
+    }
+
+    pub fn release(&mut self, key: U4) {
+        if key < 16 {
+            self.0 &= !(1 << key);
+        }
+    }
+
+    pub fn is_pressed(&self, key: U4) -> bool {
+        key < 16 && self.0 & (1 << key) != 0
+    }
+
+    // Bit n set when key n is pressed
+    pub fn bits(&self) -> u16 {
+        self.0
+    }
+
+    pub fn from_bits(bits: u16) -> Self {
+        Keypad(bits)
+    }
+}
+
+// Output of the display, called at most once per 60Hz frame
+pub trait Renderer {
+    fn render(&mut self, display: &Display);
+
+    // For renderers able to show shades, called instead of `render`
+    fn render_intensity(&mut self, display: &Display, _intensity: &Intensity) {
+        self.render(display);
+    }
+
+    // What the VM calls: only the rows whose bit is set in `dirty_rows` (bit n for row n)
+    // changed since the previous frame, for renderers which can redraw part of the screen
+    fn render_rows(&mut self, display: &Display, intensity: &Intensity, _dirty_rows: u32) {
+        self.render_intensity(display, intensity);
+    }
+
+    // One line summary of the VM (PC and cycles) given before each render, for
+    // renderers able to show it
+    fn set_status(&mut self, _status: &str) {}
+}
+// Lets the caller keep access to a renderer handed to the VM
+impl<R: Renderer + ?Sized> Renderer for alloc::rc::Rc<core::cell::RefCell<R>> {
+    fn render(&mut self, display: &Display) {
+        self.borrow_mut().render(display);
+    }
+
+    fn render_intensity(&mut self, display: &Display, intensity: &Intensity) {
+        self.borrow_mut().render_intensity(display, intensity);
+    }
+
+    fn render_rows(&mut self, display: &Display, intensity: &Intensity, dirty_rows: u32) {
+        self.borrow_mut()
+            .render_rows(display, intensity, dirty_rows);
+    }
+
+    fn set_status(&mut self, status: &str) {
+        self.borrow_mut().set_status(status);
+    }
+}
+// Same, for VMs moved to another thread (the VM only accepts `Send` renderers)
+#[cfg(feature = "std")]
+impl<R: Renderer + ?Sized> Renderer for Arc<Mutex<R>> {
+    fn render(&mut self, display: &Display) {
+        self.lock().unwrap().render(display);
+    }
+
+    fn render_intensity(&mut self, display: &Display, intensity: &Intensity) {
+        self.lock().unwrap().render_intensity(display, intensity);
+    }
+
+    fn render_rows(&mut self, display: &Display, intensity: &Intensity, dirty_rows: u32) {
+        self.lock()
+            .unwrap()
+            .render_rows(display, intensity, dirty_rows);
+    }
+
+    fn set_status(&mut self, status: &str) {
+        self.lock().unwrap().set_status(status);
+    }
+}
+
+// Either the host drives the timers through `tick_timers` (always the case without std),
+// or a background thread decrements them at 60Hz
+enum TimersHandle {
+    Manual(Timers),
+    #[cfg(feature = "std")]
+    Threaded(TimersWrapper),
+}
+impl TimersHandle {
+    fn new(options: &Chip8VMOptions) -> Self {
+        let initial = Timers {
+            delay: options.initial_delay,
+            buzzer: 0,
+        };
+        #[cfg(feature = "std")]
+        if !options.manual_timers {
+            return Self::Threaded(TimersWrapper::new(
+                initial,
+                options.debug,
+                Arc::new(SystemClock),
+            ));
+        }
+        Self::Manual(initial)
+    }
+
+    fn get(&self) -> Timers {
+        match self {
+            Self::Manual(t) => *t,
+            #[cfg(feature = "std")]
+            Self::Threaded(w) => w.timers.load(),
+        }
+    }
+
+    fn tick(&mut self) {
+        match self {
+            Self::Manual(t) => t.update(),
+            #[cfg(feature = "std")]
+            Self::Threaded(_) => {}
+        }
+    }
+
+    fn modify(&mut self, f: impl FnOnce(&mut Timers)) {
+        match self {
+            Self::Manual(t) => f(t),
+            #[cfg(feature = "std")]
+            Self::Threaded(w) => w.timers.modify(f),
+        }
+    }
+
+    #[cfg(feature = "std")]
+    fn start(&mut self) {
+        if let Self::Threaded(w) = self {
+            w.started = true;
+            w.update_running();
+        }
+    }
+
+    #[cfg(feature = "std")]
+    fn set_paused(&mut self, paused: bool) {
+        if let Self::Threaded(w) = self {
+            w.paused = paused;
+            w.update_running();
+        }
+    }
+
+    // Restarts the timer thread on `clock`, in the same state
+    #[cfg(feature = "std")]
+    fn set_clock(&mut self, clock: Arc<dyn Clock>, debug: bool) {
+        if let Self::Threaded(w) = self {
+            let mut wrapper = TimersWrapper::new(w.timers.load(), debug, clock);
+            wrapper.started = w.started;
+            wrapper.paused = w.paused;
+            wrapper.update_running();
+            *w = wrapper;
+        }
+    }
+}
+impl Debug for TimersHandle {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Manual(t) => {
+                writeln!(f, "---  Timers  ---")?;
+                writeln!(f, "delay: {} | buzzer: {}", t.delay, t.buzzer)
+            }
+            #[cfg(feature = "std")]
+            Self::Threaded(w) => write!(f, "{w:?}"),
+        }
+    }
+}
+
+// Timers shared with the timer thread. Atomics rather than a mutex so that ROMs
+// polling the delay timer in a tight loop never wait on the thread.
+#[cfg(feature = "std")]
+struct AtomicTimers {
+    delay: AtomicU8,
+    buzzer: AtomicU8,
+}
+#[cfg(feature = "std")]
+impl AtomicTimers {
+    fn new(timers: Timers) -> Self {
+        AtomicTimers {
+            delay: AtomicU8::new(timers.delay),
+            buzzer: AtomicU8::new(timers.buzzer),
+        }
+    }
+
+    fn load(&self) -> Timers {
+        Timers {
+            delay: self.delay.load(Ordering::Relaxed),
+            buzzer: self.buzzer.load(Ordering::Relaxed),
+        }
+    }
+
+    // Only the timers changed by `f` are stored, so a decrement happening meanwhile
+    // on the other one isn't lost
+    fn modify(&self, f: impl FnOnce(&mut Timers)) {
+        let before = self.load();
+        let mut after = before;
+        f(&mut after);
+        if after.delay != before.delay {
+            self.delay.store(after.delay, Ordering::Relaxed);
+        }
+        if after.buzzer != before.buzzer {
+            self.buzzer.store(after.buzzer, Ordering::Relaxed);
+        }
+    }
+
+    fn update(&self) {
+        for timer in [&self.delay, &self.buzzer] {
+            // Compare and swap loop, stops at 0 (Err) without writing
+            let _ = timer.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |t| t.checked_sub(1));
+        }
+    }
+}
+
+#[cfg(feature = "std")]
 struct TimersWrapper {
-    timers: Arc<Mutex<Timers>>,
+    timers: Arc<AtomicTimers>,
+    // Whether the thread should be decrementing the timers
     lock: Arc<(Mutex<bool>, Condvar)>,
+    stop: Arc<AtomicBool>,
+    handle: Option<thread::JoinHandle<()>>,
+    started: bool,
+    paused: bool,
 }
+#[cfg(feature = "std")]
 impl Debug for TimersWrapper {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         writeln!(f, "---  Timers  ---")?;
-        let t = self.timers.lock().unwrap();
+        let t = self.timers.load();
         writeln!(f, "delay: {} | buzzer: {}", t.delay, t.buzzer)
     }
 }
+#[cfg(feature = "std")]
 impl TimersWrapper {
-    fn new() -> Self {
-        let wrapper = TimersWrapper {
-            timers: Arc::new(Mutex::new(Timers {
-                delay: 120,
-                buzzer: 0,
-            })),
+    fn new(initial: Timers, debug: bool, clock: Arc<dyn Clock>) -> Self {
+        let mut wrapper = TimersWrapper {
+            timers: Arc::new(AtomicTimers::new(initial)),
             lock: Arc::new((Mutex::new(false), Condvar::new())),
+            stop: Arc::new(AtomicBool::new(false)),
+            handle: None,
+            started: false,
+            paused: false,
         };
         let t_clone = Arc::clone(&wrapper.timers);
         let l_clone = Arc::clone(&wrapper.lock);
-        thread::spawn(move || {
+        let s_clone = Arc::clone(&wrapper.stop);
+        wrapper.handle = Some(thread::spawn(move || {
             let timer = t_clone;
             let (lock, cvar) = &*l_clone;
-            let mut last_update = Instant::now();
-            let mut wait: f64;
+            let period = Duration::from_secs(1) / Timers::TIMER_FREQ;
+            let mut last_update = clock.now();
             loop {
                 //wait for start signal
                 let mut started = lock.lock().unwrap();
                 // As long as the value inside the `Mutex<bool>` is `false`, we wait.
-                while !*started {
-                    println!("Timers waiting to start");
+                let mut waited = false;
+                while !*started && !s_clone.load(Ordering::Relaxed) {
+                    log_debug(debug, "Timers waiting to start");
                     started = cvar.wait(started).unwrap();
-                    println!("Timers starting");
+                    waited = true;
+                    log_debug(debug, "Timers starting");
+                }
+                if s_clone.load(Ordering::Relaxed) {
+                    return;
+                }
+                // Count from the (re)start, not from before the pause
+                if waited {
+                    last_update = clock.now();
                 }
 
-                wait =
-                    (1. / Timers::TIMER_FREQ as f64 - last_update.elapsed().as_secs_f64()).max(0.);
-
-                thread::sleep(Duration::from_secs_f64(wait));
-                timer.lock().unwrap().update();
-                last_update = Instant::now();
+                let elapsed = clock.now().saturating_duration_since(last_update);
+                if elapsed < period {
+                    // Checks the pause and stop again once awake, a pause during the
+                    // sleep skips the decrement
+                    drop(started);
+                    clock.sleep(period - elapsed);
+                    continue;
+                }
+                // Still holding the lock, so a pause can't come between the check and
+                // the decrement
+                timer.update();
+                drop(started);
+                last_update = clock.now();
             }
-        });
+        }));
         wrapper
     }
+
+    fn update_running(&self) {
+        let (lock, cvar) = &*self.lock;
+        let mut running = lock.lock().unwrap();
+        *running = self.started && !self.paused;
+        // We notify the condvar that the value has changed.
+        cvar.notify_one();
+    }
+}
+#[cfg(feature = "std")]
+impl Drop for TimersWrapper {
+    fn drop(&mut self) {
+        let (lock, cvar) = &*self.lock;
+        // Hold the lock so the thread cannot miss the wake up between its checks and its wait
+        let started = lock.lock().unwrap();
+        self.stop.store(true, Ordering::Relaxed);
+        cvar.notify_one();
+        drop(started);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+// Keeps a steady rate by sleeping until absolute deadlines, so the time spent
+// executing and the sleep overshoots do not accumulate as drift
+#[cfg(feature = "std")]
+struct Pacer {
+    next_deadline: Instant,
+}
+#[cfg(feature = "std")]
+impl Pacer {
+    // Past this lag (e.g. the process was suspended) we stop trying to catch up
+    const MAX_LAG: Duration = Duration::from_millis(100);
+
+    fn new() -> Self {
+        Pacer {
+            next_deadline: Instant::now(),
+        }
+    }
+
+    // `freq` must not be 0
+    fn wait(&mut self, freq: u32) {
+        self.next_deadline += Duration::from_secs_f64(1. / freq as f64);
+        let now = Instant::now();
+        match self.next_deadline.checked_duration_since(now) {
+            Some(remaining) => thread::sleep(remaining),
+            // Behind schedule: don't sleep so the next steps catch up
+            None => self.limit_lag(now),
+        }
+    }
+
+    // Time the next `wait` sleeps until
+    fn next(&self, freq: u32) -> Instant {
+        self.next_deadline + Duration::from_secs_f64(1. / freq as f64)
+    }
+
+    // Non blocking variant of `wait`: whether the current deadline has passed,
+    // in which case the next one is scheduled
+    fn due(&mut self, freq: u32) -> bool {
+        let now = Instant::now();
+        if now < self.next_deadline {
+            return false;
+        }
+        self.next_deadline += Duration::from_secs_f64(1. / freq as f64);
+        if self.next_deadline < now {
+            self.limit_lag(now);
+        }
+        true
+    }
+
+    fn limit_lag(&mut self, now: Instant) {
+        if now - self.next_deadline > Self::MAX_LAG {
+            self.next_deadline = now;
+        }
+    }
 }
 
-#[derive(Debug, PartialEq)]
-enum Chip8Instr {
+// Decoded instruction, see `Chip8Instr::from` for the opcodes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Chip8Instr {
+    // 0NNN, call to a machine code routine of the original interpreter, ignored
+    Sys(U12),
     Clear,
     Return,
     Jump(U12),
     Call(U12),
-    IfNE(U4, u8),
-    IfE(U4, u8),
-    IfRNE(U4, U4),
+    SkipEqImm(U4, u8),
+    SkipNeImm(U4, u8),
+    SkipEqReg(U4, U4),
     Set(U4, u8),
     Add(U4, u8),
     SetR(U4, U4),
     BitOp(U4, U4, U4),
     ArithmOp(U4, U4, U4),
     ShiftOp(U4, U4, U4),
-    IfRE(U4, U4),
+    SkipNeReg(U4, U4),
     SetI(U12),
     JumpOff(U12),
     Rand(U4, u8),
@@ -203,11 +648,90 @@ enum Chip8Instr {
     SetBuzzer(U4),
     IncrI(U4),
     Char(U4),
+    // FX30 (SUPER-CHIP), I = address of the big glyph of VX
+    BigChar(U4),
     Decimal(U4),
     Save(U4),
     Load(U4),
     Unknown,
 }
+impl Chip8Instr {
+    // Registers used as VX and VY (0 when there is none)
+    fn registers(&self) -> [U4; 2] {
+        match *self {
+            Self::SkipEqReg(x, y)
+            | Self::SetR(x, y)
+            | Self::BitOp(x, y, _)
+            | Self::ArithmOp(x, y, _)
+            | Self::ShiftOp(x, y, _)
+            | Self::SkipNeReg(x, y)
+            | Self::Display(x, y, _) => [x, y],
+            Self::SkipEqImm(x, _)
+            | Self::SkipNeImm(x, _)
+            | Self::Set(x, _)
+            | Self::Add(x, _)
+            | Self::Rand(x, _)
+            | Self::KeyUp(x)
+            | Self::KeyDown(x)
+            | Self::GetDelay(x)
+            | Self::GetKey(x)
+            | Self::SetDelay(x)
+            | Self::SetBuzzer(x)
+            | Self::IncrI(x)
+            | Self::Char(x)
+            | Self::BigChar(x)
+            | Self::Decimal(x)
+            | Self::Save(x)
+            | Self::Load(x) => [x, 0],
+            Self::Sys(_)
+            | Self::Clear
+            | Self::Return
+            | Self::Jump(_)
+            | Self::Call(_)
+            | Self::SetI(_)
+            | Self::JumpOff(_)
+            | Self::Unknown => [0, 0],
+        }
+    }
+
+    #[cfg(feature = "std")]
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Sys(_) => "Sys",
+            Self::Clear => "Clear",
+            Self::Return => "Return",
+            Self::Jump(_) => "Jump",
+            Self::Call(_) => "Call",
+            Self::SkipEqImm(..) => "SkipEqImm",
+            Self::SkipNeImm(..) => "SkipNeImm",
+            Self::SkipEqReg(..) => "SkipEqReg",
+            Self::Set(..) => "Set",
+            Self::Add(..) => "Add",
+            Self::SetR(..) => "SetR",
+            Self::BitOp(..) => "BitOp",
+            Self::ArithmOp(..) => "ArithmOp",
+            Self::ShiftOp(..) => "ShiftOp",
+            Self::SkipNeReg(..) => "SkipNeReg",
+            Self::SetI(_) => "SetI",
+            Self::JumpOff(_) => "JumpOff",
+            Self::Rand(..) => "Rand",
+            Self::Display(..) => "Display",
+            Self::KeyUp(_) => "KeyUp",
+            Self::KeyDown(_) => "KeyDown",
+            Self::GetDelay(_) => "GetDelay",
+            Self::GetKey(_) => "GetKey",
+            Self::SetDelay(_) => "SetDelay",
+            Self::SetBuzzer(_) => "SetBuzzer",
+            Self::IncrI(_) => "IncrI",
+            Self::Char(_) => "Char",
+            Self::BigChar(_) => "BigChar",
+            Self::Decimal(_) => "Decimal",
+            Self::Save(_) => "Save",
+            Self::Load(_) => "Load",
+            Self::Unknown => "Unknown",
+        }
+    }
+}
 impl From<u16> for Chip8Instr {
     fn from(input: u16) -> Self {
         let x = (input >> 8 & 0xF) as U4;
@@ -216,32 +740,34 @@ impl From<u16> for Chip8Instr {
         let nn = (input & 0xFF) as u8;
         let nnn = (input & 0xFFF) as U12;
         match input >> 12 {
-            0 if n == 0xE => Self::Return,
-            0 => Self::Clear,
+            0 if nnn == 0x0E0 => Self::Clear,
+            0 if nnn == 0x0EE => Self::Return,
+            0 => Self::Sys(nnn),
             1 => Self::Jump(nnn),
             2 => Self::Call(nnn),
-            3 => Self::IfNE(x, nn),
-            4 => Self::IfE(x, nn),
-            5 => Self::IfRNE(x, y),
+            3 => Self::SkipEqImm(x, nn),
+            4 => Self::SkipNeImm(x, nn),
+            5 if n == 0 => Self::SkipEqReg(x, y),
             6 => Self::Set(x, nn),
             7 => Self::Add(x, nn),
             8 if n == 0 => Self::SetR(x, y),
             8 if n < 4 => Self::BitOp(x, y, n),
             8 if n == 6 || n == 0xE => Self::ShiftOp(x, y, n),
-            8 => Self::ArithmOp(x, y, n),
-            9 => Self::IfRE(x, y),
+            8 if n == 4 || n == 5 || n == 7 => Self::ArithmOp(x, y, n),
+            9 if n == 0 => Self::SkipNeReg(x, y),
             0xA => Self::SetI(nnn),
             0xB => Self::JumpOff(nnn),
             0xC => Self::Rand(x, nn),
             0xD => Self::Display(x, y, n),
-            0xE if n == 0xE => Self::KeyUp(x),
-            0xE => Self::KeyDown(x),
+            0xE if nn == 0x9E => Self::KeyDown(x),
+            0xE if nn == 0xA1 => Self::KeyUp(x),
             0xF if nn == 0x07 => Self::GetDelay(x),
             0xF if nn == 0x0A => Self::GetKey(x),
             0xF if nn == 0x15 => Self::SetDelay(x),
             0xF if nn == 0x18 => Self::SetBuzzer(x),
             0xF if nn == 0x1E => Self::IncrI(x),
             0xF if nn == 0x29 => Self::Char(x),
+            0xF if nn == 0x30 => Self::BigChar(x),
             0xF if nn == 0x33 => Self::Decimal(x),
             0xF if nn == 0x55 => Self::Save(x),
             0xF if nn == 0x65 => Self::Load(x),
@@ -250,52 +776,452 @@ impl From<u16> for Chip8Instr {
     }
 }
 
-#[derive(Default)]
+// Quirks of the main CHIP-8 interpreters. Options set explicitly still win:
+// `Chip8VMOptions { old_shift: false, ..Profile::CosmacVip.options() }`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Profile {
+    CosmacVip,
+    Chip48,
+    SuperChip,
+    XoChip,
+}
+impl Profile {
+    pub fn options(self) -> Chip8VMOptions {
+        // FX55/FX65 leave I on the last register (CHIP-48) or after it (VIP, XO-CHIP)
+        let (incr_i_when_mem, incr_i_past_last) = match self {
+            Self::CosmacVip | Self::XoChip => (true, true),
+            Self::Chip48 => (true, false),
+            Self::SuperChip => (false, false),
+        };
+        let (old_shift, new_jump_off, vf_reset, display_wait, wrap_sprites) = match self {
+            Self::CosmacVip => (true, false, true, true, false),
+            Self::Chip48 => (false, true, false, false, false),
+            Self::SuperChip => (false, true, false, false, false),
+            Self::XoChip => (true, false, false, false, true),
+        };
+        Chip8VMOptions {
+            old_shift,
+            new_jump_off,
+            incr_i_when_mem,
+            incr_i_past_last,
+            vf_reset,
+            display_wait,
+            wrap_sprites,
+            ..Default::default()
+        }
+    }
+}
+
+// Loaded ROMs that are probably corrupt files
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RomWarning {
+    // Not even one instruction
+    TooSmall,
+    // Instructions are two bytes, real ROMs rarely end on a single one
+    OddLength,
+    // The first instruction is not a valid opcode but would be with its two bytes
+    // swapped, the file may be a little-endian dump (see the `byte_swap` option).
+    // Only a hint: a swapped ROM starting with an opcode valid both ways isn't detected.
+    ByteSwapped,
+    // SUPER-CHIP or XO-CHIP opcodes were found, with the `detect_extensions` option
+    Extension(Extension),
+}
+impl core::fmt::Display for RomWarning {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::TooSmall => write!(f, "the ROM is smaller than one instruction"),
+            Self::OddLength => write!(f, "the ROM has an odd length, the file may be truncated"),
+            Self::ByteSwapped => write!(
+                f,
+                "the first instruction is invalid, the ROM may be byte swapped"
+            ),
+            Self::Extension(extension) => write!(
+                f,
+                "the ROM seems to use {extension} opcodes, try the {:?} profile",
+                extension.profile()
+            ),
+        }
+    }
+}
+
+// Instruction set extending CHIP-8, see `detect_extensions`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Extension {
+    SuperChip,
+    XoChip,
+}
+impl Extension {
+    // Profile to run the ROMs using the extension with
+    pub fn profile(self) -> Profile {
+        match self {
+            Self::SuperChip => Profile::SuperChip,
+            Self::XoChip => Profile::XoChip,
+        }
+    }
+}
+impl core::fmt::Display for Extension {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::SuperChip => write!(f, "SUPER-CHIP"),
+            Self::XoChip => write!(f, "XO-CHIP"),
+        }
+    }
+}
+
+// Control flow instruction found by `Chip8VM::control_targets`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TargetKind {
+    Jump,
+    Call,
+    // BNNN, the target is NNN plus a register only known at run time
+    // (the reported target is NNN)
+    Dynamic,
+}
+
+// Why `Chip8VM::step` or `Chip8VM::continue_to` returned
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StopReason {
+    // Ran all the instructions asked for (for `continue_to`, `STEP_OVER_LIMIT` of them
+    // without reaching the address)
+    Count,
+    // PC reached the `continue_to` address
+    Reached,
+    // PC is on this breakpoint, the instruction there is not run yet
+    Breakpoint(u16),
+    // See `halt_on_self_jump`
+    Halted,
+    Error(Chip8Error),
+}
+
+// Span of the RAM from `start` to `end` (excluded), see `Chip8VM::memory_map`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemRegion {
+    pub kind: MemRegionKind,
+    pub start: u16,
+    pub end: u16,
+}
+impl MemRegion {
+    pub fn contains(&self, addr: u16) -> bool {
+        (self.start..self.end).contains(&addr)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MemRegionKind {
+    // Used by the original interpreter, unused here apart from the fonts
+    Reserved,
+    // Small hex digits, used by FX29
+    Font,
+    // SUPER-CHIP hex digits, see `Chip8VM::big_char_index`
+    BigFont,
+    // The last loaded ROM
+    Rom,
+    // After the ROM, free for the program
+    Free,
+}
+
+// Recorded with the `record_events` option, see `Chip8VM::take_events`.
+// `frame` counts the 60Hz frames (`tick_timers` calls) and `pc` is the address of the
+// instruction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VmEvent {
+    Clear { frame: u64, pc: u16 },
+    // A sprite drawn at (`x`, `y`) turned off a pixel, setting VF to 1
+    Collision { frame: u64, pc: u16, x: u8, y: u8 },
+    // Opcode ignored by the `skip_unknown` option
+    UnknownOpcode { frame: u64, pc: u16, opcode: u16 },
+}
+
+// How the instructions are spread over time
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TimingModel {
+    // One instruction every 1/`freq` second
+    #[default]
+    Frequency,
+    // Bursts of exactly this many instructions at the start of every 60Hz frame,
+    // `freq` is ignored. Steadier frame pacing, as in most modern emulators.
+    CyclesPerFrame(u32),
+}
+
+#[derive(Default, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+// Options missing from older saved states take their default value
+#[cfg_attr(feature = "serde", serde(default))]
 pub struct Chip8VMOptions {
     //Debug/Output options
     pub hide_display: bool,
     pub debug: bool,
     pub debug_ram: bool,
+    pub debug_stack: bool,
     pub keep_display: bool,
+    //Frame the terminal display with a border (`TerminalBorder::default`, other characters
+    //through `TerminalRenderer::with_border`)
+    pub terminal_border: bool,
+    //Print PC and the cycle count above the terminal display
+    pub terminal_status: bool,
+    //Count executions of each instruction and address, see `opcode_stats` and `run_stats`
+    pub opcode_stats: bool,
 
-    //Ambiguous instructions toggle
+    //Ambiguous instructions toggle, see `Profile` for the usual combinations
+    //FX55 and FX65 move I: by X (CHIP-48), or by X + 1 with `incr_i_past_last`
+    //(COSMAC VIP, XO-CHIP)
     pub incr_i_when_mem: bool,
+    pub incr_i_past_last: bool,
     pub new_jump_off: bool,
+    //8XY6 and 8XYE shift VY into VX (COSMAC VIP) instead of shifting VX in place.
+    //Either way VF gets the bit shifted out of the source (bit 0 for 6, bit 7 for E) and
+    //is written last, so it holds that bit even when X is F.
     pub old_shift: bool,
+    //8XY1, 8XY2 and 8XY3 reset VF
+    pub vf_reset: bool,
+    //PC and I wrap around at 0xFFF instead of raising `Chip8Error::AddressOverflow`
+    pub wrap_addresses: bool,
+    //Draw at most one sprite per frame, waiting for the next one
+    pub display_wait: bool,
+    //Sprites crossing the edge of the screen wrap around instead of being clipped
+    pub wrap_sprites: bool,
+    //Sprites starting off screen (VX >= 64 or VY >= 32) are not drawn at all, instead of
+    //starting at the coordinates modulo the screen size like in most interpreters.
+    //Independent from `wrap_sprites`, which applies to the sprites drawn.
+    pub clip_sprite_start: bool,
+
+    //Number of frames a pixel takes to fade out once turned off, to reduce the flicker
+    //of XOR drawing with renderers supporting shades (0 turns pixels off immediately)
+    pub phosphor_frames: u8,
+
+    //Frequency (the default) or fixed number of instructions per frame
+    pub timing: TimingModel,
+
+    //Address where the ROM is loaded and execution starts (RAM_ROM_START by default,
+    //0x600 for ETI-660 programs)
+    pub rom_start: Option<U12>,
+
+    //Timers are only advanced by calls to `tick_timers` instead of a background thread,
+    //for deterministic tests and hosts driving the frames themselves (threaded by default)
+    pub manual_timers: bool,
+    //Value of the delay timer at startup (0 per spec)
+    pub initial_delay: u8,
+
+    //Number of instructions that can be undone with `step_back` (0 disables the history)
+    pub history_depth: usize,
+    //Instructions writing to the RAM below 0x200 (interpreter and fonts) fail with
+    //`Chip8Error::WriteProtected` instead of corrupting it
+    pub protect_reserved: bool,
+    //A jump to itself (the usual end of a ROM) halts the VM and makes `run` return.
+    //Off by default, some ROMs spin this way while waiting for the timers.
+    pub halt_on_self_jump: bool,
+    //`run` sleeps until the next frame instead of spinning in a loop that only polls the
+    //delay timer or the keys, which can't exit before the timers tick or a key is read.
+    //Fewer instructions are executed (see `cycles`) but the ROM behaves the same.
+    pub idle_sleep: bool,
+    //Keep the decoded instruction of each address until the RAM there is written,
+    //instead of decoding at every cycle
+    pub decode_cache: bool,
+    //Keep a log of the display clears and sprite collisions, see `take_events`
+    pub record_events: bool,
+    //Check the machine invariants at every cycle (see `check_invariants`) and fail with
+    //`Chip8Error::BrokenInvariant` as soon as one breaks, a development aid
+    pub strict: bool,
+    //Opcodes that don't decode to any instruction are skipped instead of failing with
+    //`Chip8Error::UnknownOpcode`, logged with `debug` and as `VmEvent::UnknownOpcode`
+    //with `record_events`
+    pub skip_unknown: bool,
+    //Swap the two bytes of each instruction when loading a ROM, for little-endian dumps
+    pub byte_swap: bool,
+    //Let `load_bytes_at` write over the fonts
+    pub allow_font_overwrite: bool,
+    //Clear bit 0 of PC before fetching, so a jump to an odd address runs the instruction
+    //just before it. Off by default: the 2 bytes at the odd address are fetched, which
+    //some self-modifying ROMs rely on (the first such fetch is logged with `debug`).
+    pub align_pc: bool,
+    //Scan the ROM for SUPER-CHIP and XO-CHIP opcodes when loading it and warn if there
+    //are some (`RomWarning::Extension`), see `detect_extensions`
+    pub detect_extensions: bool,
+
+    //Input options
+    //Read the keypad from stdin while running (keys 0-9, a-f)
+    pub terminal_keypad: bool,
+    //Time without key repeat after which a key is considered released
+    //(defaults to `TerminalInput::DEFAULT_HOLD_TIMEOUT`)
+    pub key_hold_timeout: Option<Duration>,
+}
+
+// Gets the display of the frame and the keypad to inject input.
+// It runs inside `run` while the VM is borrowed, so it must not try to drive the VM itself
+// (calling `run` or `run_once` again through some shared handle).
+#[cfg(feature = "std")]
+pub type FrameCallback = Box<dyn FnMut(&Display, &mut Keypad) + Send>;
+
+// Called with true when the buzzer starts and false when it stops, see
+// `Chip8VM::set_on_buzzer`
+pub type BuzzerCallback = Box<dyn FnMut(bool) + Send>;
+
+// Returns the keys held in the host's own event loop, as a bitmask (bit n for key n).
+// See `Chip8VM::set_key_poll`.
+#[cfg(feature = "std")]
+pub type KeyPoll = Box<dyn FnMut() -> u16 + Send>;
+
+// Signaled by `run` at the end of each 60Hz frame, once the display is presented,
+// and once more when `run` returns.
+// Handles are cloned from `Chip8VM::frame_signal` and moved to a render thread, which
+// can sleep in `wait_for_frame` instead of polling.
+#[cfg(feature = "std")]
+#[derive(Clone, Default)]
+pub struct FrameSignal {
+    state: Arc<(Mutex<FrameState>, Condvar)>,
+}
+#[cfg(feature = "std")]
+#[derive(Default)]
+struct FrameState {
+    // Number of the last finished frame
+    frame: u64,
+    // `run` returned (halt or error), no frame is coming until the next `pre_run`
+    stopped: bool,
+}
+#[cfg(feature = "std")]
+impl FrameSignal {
+    // Blocks until the next frame ends and returns its number (see `VmEvent`), or
+    // returns None once `run` has returned.
+    // Must not be called from the thread running the VM, which would never get to
+    // the end of the frame.
+    pub fn wait_for_frame(&self) -> Option<u64> {
+        let (lock, cvar) = &*self.state;
+        let state = lock.lock().unwrap();
+        let start = state.frame;
+        let state = cvar
+            .wait_while(state, |state| state.frame == start && !state.stopped)
+            .unwrap();
+        (state.frame != start).then_some(state.frame)
+    }
+
+    fn notify(&self, frame: u64) {
+        let (lock, cvar) = &*self.state;
+        lock.lock().unwrap().frame = frame;
+        cvar.notify_all();
+    }
+
+    fn set_stopped(&self, stopped: bool) {
+        let (lock, cvar) = &*self.state;
+        lock.lock().unwrap().stopped = stopped;
+        cvar.notify_all();
+    }
 }
 
 pub struct Chip8VM {
     // 4kB of memory
     ram: Ram,
 
-    // Display of 64*32 pixels (On or Off)
+    // Display of 64*32 pixels (On or Off).
+    // Back buffer: the drawing instructions modify it, `present` copies it to `front`
     pub display: Display,
+    // Second XO-CHIP plane, `display` being the first. Not rendered yet.
+    plane2: Display,
+    // Planes the drawing instructions work on, bit 0 for `display` and bit 1 for
+    // `plane2`, see `select_planes`
+    planes: u8,
+    // What was last presented, only updated between frames so it never shows a
+    // partially drawn frame
+    front: Display,
+    intensity: Intensity,
+    // Rows changed by the last `present`, one bit per row
+    dirty_rows: u32,
+    // Some pixels of `intensity` are still fading out
+    fading: bool,
+
+    // Display modified since the last presented frame
+    display_changed: bool,
 
     //All registers
     registers: Registers,
 
     //Timers
-    timers: TimersWrapper,
+    timers: TimersHandle,
 
     //Stack
     stack: Vec<U12>,
 
-    //Clock speed (Hz)
+    //Keys currently held down
+    keypad: Keypad,
+    // Key pressed during a GetKey, which completes once it is released
+    key_wait: Option<u8>,
+    // No sprite drawn since the start of the frame, see `display_wait`
+    vblank: bool,
+    paused: bool,
+    turbo: bool,
+    // 60Hz frames since the VM was created
+    frames: u64,
+    events: Vec<VmEvent>,
+    // Reached a jump to itself, see `halt_on_self_jump`
+    halted: bool,
+    // An instruction was fetched from an odd address, only reported the first time
+    unaligned_fetch: bool,
+    // Addresses where `step` and `continue_to` stop
+    breakpoints: BTreeSet<u16>,
+    history: history::History,
+    // Decoded instruction starting at each address, see `decode_cache`
+    decoded: Option<Box<[Option<Chip8Instr>]>>,
+
+    //Clock speed (Hz), prefer `set_freq` which checks it. Values outside of
+    //`MIN_FREQ..=MAX_FREQ` are clamped when running.
     pub freq: u32,
 
     //Misc options
     options: Chip8VMOptions,
+
+    //Instructions executed so far
+    cycles: u64,
+    // Size of the last loaded ROM
+    rom_len: usize,
+    // File of the last ROM loaded with `load_rom_from_file`, see `reload_rom_from_file`
+    #[cfg(feature = "std")]
+    rom_path: Option<String>,
+    #[cfg(feature = "std")]
+    opcode_counts: HashMap<&'static str, u64>,
+    #[cfg(feature = "std")]
+    pc_counts: HashMap<U12, u64>,
+
+    //Keypad fed from stdin, see the `terminal_keypad` option
+    #[cfg(feature = "std")]
+    terminal_input: Option<TerminalInput>,
+
+    //Presents the display at the end of frames where it changed
+    #[cfg(feature = "std")]
+    renderer: Option<Box<dyn Renderer + Send>>,
+    // Rate limit of `present`: last call of the renderer
+    #[cfg(feature = "std")]
+    last_render: Option<Instant>,
+
+    //Called by `run` at every 60Hz frame
+    #[cfg(feature = "std")]
+    on_frame: Option<FrameCallback>,
+    #[cfg(feature = "std")]
+    key_poll: Option<KeyPoll>,
+    on_buzzer: Option<BuzzerCallback>,
+    // Last state given to `on_buzzer`
+    buzzing: bool,
+    #[cfg(feature = "std")]
+    frame_signal: FrameSignal,
+
+    //Execution trace output, see `set_trace`
+    #[cfg(feature = "std")]
+    trace: Option<trace::TraceSink>,
+    //Session being recorded, see `record_to`
+    #[cfg(feature = "std")]
+    recorder: Option<replay::Recorder>,
+
+    //Source for the Rand instruction, the thread rng when None
+    rng: Option<Box<dyn RngCore + Send>>,
 }
-impl std::fmt::Debug for Chip8VM {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+impl core::fmt::Debug for Chip8VM {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         writeln!(f, "{:?}", self.timers)?;
         writeln!(f, "{:?}", self.registers)?;
-        // writeln!(f, "--- Stack ---\n{:?}", self.stack)?;
-        let r = writeln!(
-            f,
-            "Next instruction: {:?}",
-            Chip8Instr::from(self.fetch_instruction())
-        );
+        if self.options.debug_stack {
+            writeln!(f, "--- Stack ---\n{:x?}", self.stack)?;
+        }
+        let r = writeln!(f, "Next instruction: {:?}", self.peek_instruction().1);
         if self.options.debug_ram {
             writeln!(f, "--- RAM dump ---")?;
             for i in 0..Self::RAM_DISP_LINES - 1 {
@@ -315,8 +1241,8 @@ impl std::fmt::Debug for Chip8VM {
         }
     }
 }
-impl std::fmt::Display for Chip8VM {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+impl core::fmt::Display for Chip8VM {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         writeln!(f, "{:#?}", self.display)
     }
 }
@@ -325,14 +1251,28 @@ impl Chip8VM {
     const RAM_DISP_LINES: usize = Self::RAM_SIZE / Self::RAM_DISP_LINE_WIDTH;
 
     const FREQ: u32 = 700;
+    // Range accepted by `set_freq`
+    pub const MIN_FREQ: u32 = 1;
+    pub const MAX_FREQ: u32 = 10_000_000;
+
+    pub const STEP_OVER_LIMIT: u32 = 1_000_000;
 
-    const RAM_SIZE: usize = 4096;
+    // Nesting of calls allowed by the `strict` option, as on the original interpreter
+    pub const STACK_LIMIT: usize = 16;
 
-    const RAM_ROM_START: usize = 0x200;
+    #[cfg(not(feature = "std"))]
+    const RNG_SEED: u64 = 0xC8;
 
-    const FONT_SIZE: usize = 80;
+    // Machine parameters, for frontends and tools. `DISPLAY_WIDTH` and `DISPLAY_HEIGHT`
+    // are the CHIP-8 mode, buffers following the display should use `display_size`.
+    pub const RAM_SIZE: usize = 4096;
 
-    const FONT_START: usize = 0x50;
+    // Default ROM load address, see `rom_start`
+    pub const RAM_ROM_START: usize = 0x200;
+
+    pub const FONT_SIZE: usize = 80;
+
+    pub const FONT_START: usize = 0x50;
     const FONT: [u8; Self::FONT_SIZE] = [
         0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
         0x20, 0x60, 0x20, 0x20, 0x70, // 1
@@ -352,112 +1292,1143 @@ impl Chip8VM {
         0xF0, 0x80, 0xF0, 0x80, 0x80, // F
     ];
 
-    const DISPLAY_WIDTH: usize = 64;
-    const DISPLAY_HEIGHT: usize = 32;
+    // SUPER-CHIP 8x10 digits, right after the small font
+    pub const BIG_FONT_SIZE: usize = 160;
+    pub const BIG_FONT_START: usize = Self::FONT_START + Self::FONT_SIZE;
+    const BIG_FONT: [u8; Self::BIG_FONT_SIZE] = [
+        0xFF, 0xFF, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, // 0
+        0x18, 0x78, 0x78, 0x18, 0x18, 0x18, 0x18, 0x18, 0xFF, 0xFF, // 1
+        0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, // 2
+        0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 3
+        0xC3, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0x03, 0x03, 0x03, 0x03, // 4
+        0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 5
+        0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, // 6
+        0xFF, 0xFF, 0x03, 0x03, 0x06, 0x0C, 0x18, 0x18, 0x18, 0x18, // 7
+        0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, // 8
+        0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 9
+        0x7E, 0xFF, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xC3, // A
+        0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, // B
+        0x3C, 0xFF, 0xC3, 0xC0, 0xC0, 0xC0, 0xC0, 0xC3, 0xFF, 0x3C, // C
+        0xFC, 0xFE, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFE, 0xFC, // D
+        0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, // E
+        0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xC0, 0xC0, // F
+    ];
+
+    pub const DISPLAY_WIDTH: usize = 64;
+    pub const DISPLAY_HEIGHT: usize = 32;
     const DISPLAY_EMPTY: Display = [[false; Self::DISPLAY_WIDTH]; Self::DISPLAY_HEIGHT];
 
     pub fn new(freq: Option<u32>, font: Option<Font>, options: Option<Chip8VMOptions>) -> Self {
+        let options = options.unwrap_or_default();
+        let font = font.unwrap_or(Self::FONT);
+        if let Err(e) = Self::validate_font(&font) {
+            panic!("{e}");
+        }
+        let rom_start = options.rom_start.unwrap_or(Self::RAM_ROM_START as U12);
+        assert!(
+            (rom_start as usize) < Self::RAM_SIZE,
+            "ROM start {rom_start:#x} is outside of RAM"
+        );
         Chip8VM {
-            ram: Chip8VM::init_ram(font.unwrap_or(Self::FONT)),
+            ram: Chip8VM::init_ram(font),
             display: Self::DISPLAY_EMPTY,
+            plane2: Self::DISPLAY_EMPTY,
+            planes: 1,
+            front: Self::DISPLAY_EMPTY,
+            intensity: [[0; Self::DISPLAY_WIDTH]; Self::DISPLAY_HEIGHT],
+            dirty_rows: 0,
+            fading: false,
+            display_changed: false,
             registers: Registers {
-                pc: U12::try_from(Self::RAM_ROM_START).expect("RAM_ROM_START is small enough"),
+                pc: rom_start,
                 ..Registers::default()
             },
-            timers: TimersWrapper::new(),
+            timers: TimersHandle::new(&options),
             stack: Vec::new(),
+            keypad: Keypad::default(),
+            key_wait: None,
+            vblank: false,
+            paused: false,
+            turbo: false,
+            frames: 0,
+            events: Vec::new(),
+            halted: false,
+            unaligned_fetch: false,
+            breakpoints: BTreeSet::new(),
+            history: history::History::new(),
+            decoded: options
+                .decode_cache
+                .then(|| alloc::vec![None; Self::RAM_SIZE].into_boxed_slice()),
+            cycles: 0,
+            rom_len: 0,
+            #[cfg(feature = "std")]
+            rom_path: None,
+            #[cfg(feature = "std")]
+            opcode_counts: HashMap::new(),
+            #[cfg(feature = "std")]
+            pc_counts: HashMap::new(),
+            #[cfg(feature = "std")]
+            renderer: if options.hide_display {
+                None
+            } else {
+                let mut renderer = TerminalRenderer::new(options.keep_display);
+                if options.terminal_border {
+                    renderer = renderer.with_border(TerminalBorder::default());
+                }
+                if options.terminal_status {
+                    renderer = renderer.with_status_line();
+                }
+                Some(Box::new(renderer))
+            },
+            #[cfg(feature = "std")]
+            last_render: None,
+            #[cfg(feature = "std")]
+            terminal_input: None,
+            #[cfg(feature = "std")]
+            on_frame: None,
+            #[cfg(feature = "std")]
+            key_poll: None,
+            on_buzzer: None,
+            buzzing: false,
+            #[cfg(feature = "std")]
+            frame_signal: FrameSignal::default(),
+            #[cfg(feature = "std")]
+            trace: None,
+            #[cfg(feature = "std")]
+            recorder: None,
             freq: freq.unwrap_or(Self::FREQ),
-            options: options.unwrap_or_default(),
+            options,
+            #[cfg(feature = "std")]
+            rng: None,
+            //No thread rng without std, fall back to a fixed seed
+            #[cfg(not(feature = "std"))]
+            rng: Some(Box::new(StdRng::seed_from_u64(Self::RNG_SEED))),
         }
     }
 
+    // Panics if the ROM doesn't fit, see `try_load_rom`
     pub fn load_rom(&mut self, rom: &[u8]) {
-        assert!(
-            rom.len() <= Self::RAM_SIZE - Self::RAM_ROM_START,
-            "Rom to big: {}B for {}B available",
-            rom.len(),
-            Self::RAM_SIZE - Self::RAM_ROM_START
-        );
-        self.debugln(&format!("Loaded rom of size {}B", rom.len()));
-        self.ram[Self::RAM_ROM_START..(Self::RAM_ROM_START + rom.len())]
-            .copy_from_slice(&rom[..(Self::RAM_ROM_START + rom.len() - Self::RAM_ROM_START)]);
+        if let Err(e) = self.try_load_rom(rom) {
+            panic!("{e}");
+        }
+    }
+
+    // Copies the ROM at the load address (`rom_start`), if it fits before the end of the RAM.
+    // ROMs loading fine but likely to be corrupt get a warning, which is also printed in
+    // debug mode.
+    pub fn try_load_rom(&mut self, rom: &[u8]) -> Result<Option<RomWarning>, Chip8Error> {
+        let start = self.rom_start();
+        let available = Self::RAM_SIZE - start;
+        if rom.len() > available {
+            return Err(Chip8Error::RomTooBig(rom.len(), available));
+        }
+        self.debugln(&format!("Loaded rom of size {}B at {start:#x}", rom.len()));
+        let loaded = &mut self.ram[start..(start + rom.len())];
+        loaded.copy_from_slice(rom);
+        if self.options.byte_swap {
+            for pair in loaded.chunks_exact_mut(2) {
+                pair.swap(0, 1);
+            }
+        }
+        self.ram_written(start, start + rom.len());
+        self.rom_len = rom.len();
+
+        let warning = if rom.len() < 2 {
+            Some(RomWarning::TooSmall)
+        } else if rom.len() % 2 == 1 {
+            Some(RomWarning::OddLength)
+        } else {
+            let first = u16::from_be_bytes([self.ram[start], self.ram[start + 1]]);
+            let swapped_is_valid = Chip8Instr::from(first.swap_bytes()) != Chip8Instr::Unknown;
+            if Chip8Instr::from(first) == Chip8Instr::Unknown && swapped_is_valid {
+                Some(RomWarning::ByteSwapped)
+            } else if self.options.detect_extensions {
+                // The loaded bytes, swapped with `byte_swap`
+                let loaded = &self.ram[start..(start + rom.len())];
+                scan_extensions(loaded, start)
+                    .last()
+                    .map(|&extension| RomWarning::Extension(extension))
+            } else {
+                None
+            }
+        };
+        if let Some(warning) = warning {
+            self.warnln(&format!("{warning}"));
+        }
+        Ok(warning)
+    }
+
+    // Copies `bytes` at `addr`, e.g. to stage sprite tables or overlays next to the ROM.
+    // Nothing is written if it doesn't fit in the RAM, or if it would overwrite the fonts
+    // without the `allow_font_overwrite` option.
+    pub fn load_bytes_at(&mut self, addr: u16, bytes: &[u8]) -> Result<(), Chip8Error> {
+        let start = addr as usize;
+        let end = start + bytes.len();
+        if end > Self::RAM_SIZE {
+            return Err(Chip8Error::OutOfBounds(end.max(start + 1) - 1));
+        }
+        let fonts = Self::FONT_START..Self::BIG_FONT_START + Self::BIG_FONT_SIZE;
+        if !self.options.allow_font_overwrite && start < fonts.end && fonts.start < end {
+            return Err(Chip8Error::WriteProtected(start.max(fonts.start)));
+        }
+        self.debugln(&format!("Loaded {}B at {start:#x}", bytes.len()));
+        self.ram[start..end].copy_from_slice(bytes);
+        self.ram_written(start, end);
+        Ok(())
+    }
+
+    // Width and height in pixels of the current display mode (only the 64x32 CHIP-8
+    // mode is emulated so far)
+    pub fn display_size(&self) -> (usize, usize) {
+        (Self::DISPLAY_WIDTH, Self::DISPLAY_HEIGHT)
+    }
+
+    pub fn rom_start(&self) -> usize {
+        self.options
+            .rom_start
+            .map_or(Self::RAM_ROM_START, |start| start as usize)
     }
 
+    #[cfg(feature = "std")]
     pub fn load_rom_from_file(&mut self, rom: &str) {
         self.debugln(&format!("Loading rom from file '{rom}'"));
         let f = std::fs::File::open(rom).expect("file exists");
         let mut reader = std::io::BufReader::new(f);
-        let mut rom: Vec<u8> = Vec::new();
+        let mut bytes: Vec<u8> = Vec::new();
 
-        reader.read_to_end(&mut rom).expect("can read file");
+        reader.read_to_end(&mut bytes).expect("can read file");
 
-        self.load_rom(&rom);
-    }
-    pub fn pre_run(&mut self) {
-        let (lock, cvar) = &*self.timers.lock;
-        let mut started = lock.lock().unwrap();
-        *started = true;
-        // We notify the condvar that the value has changed.
-        cvar.notify_one();
+        self.load_rom(&bytes);
+        self.rom_path = Some(rom.to_string());
     }
-    pub fn run_once(&mut self) {
-        let instruction = self.fetch_instruction();
-        self.debug(&format!("input (raw,decoded): {instruction:x},"));
 
-        let instruction = Chip8Instr::from(instruction);
-        self.debugln(&format!("{instruction:?}"));
-        self.incr_pc();
-        self.execute(instruction);
-        self.debugln(&format!("{self:?}"));
+    // Reads again the file of the last `load_rom_from_file`, e.g. after editing the ROM,
+    // and restarts it from a `reset`. The RAM after the load address is cleared first,
+    // so nothing is left of the previous version if the file shrank.
+    // If there is no such file, it can't be read or it doesn't fit anymore,
+    // the error is returned and the VM keeps running the previous version.
+    #[cfg(feature = "std")]
+    pub fn reload_rom_from_file(&mut self) -> std::io::Result<Option<RomWarning>> {
+        use std::io::{Error, ErrorKind};
+
+        let path = self
+            .rom_path
+            .clone()
+            .ok_or_else(|| Error::new(ErrorKind::NotFound, "no ROM loaded from a file"))?;
+        self.debugln(&format!("Reloading rom from file '{path}'"));
+        let rom = std::fs::read(&path)?;
+        self.restart(&rom)
+            .map_err(|e| Error::new(ErrorKind::InvalidData, e))
     }
-    pub fn run(&mut self) {
-        self.pre_run();
-        loop {
-            let time_start = Instant::now();
-            let instruction = self.fetch_instruction();
-            self.debug(&format!("input (raw,decoded): {instruction:x},"));
-
-            let instruction = Chip8Instr::from(instruction);
-            self.debugln(&format!("{instruction:?}"));
-            self.incr_pc();
-            self.execute(instruction);
-            self.debugln(&format!("{self:?}"));
-            thread::sleep(Duration::from_secs_f64(
-                (1. / self.freq as f64 - time_start.elapsed().as_secs_f64()).max(0.),
-            ));
+
+    // Back to the power-on state with `rom` loaded, the fonts and options are kept.
+    // The RAM after the load address is cleared first. A ROM that doesn't fit is an
+    // error and leaves the VM untouched.
+    #[cfg(feature = "std")]
+    fn restart(&mut self, rom: &[u8]) -> Result<Option<RomWarning>, Chip8Error> {
+        let start = self.rom_start();
+        if rom.len() > Self::RAM_SIZE - start {
+            return Err(Chip8Error::RomTooBig(rom.len(), Self::RAM_SIZE - start));
         }
+        self.ram[start..].fill(0);
+        self.ram_written(start, Self::RAM_SIZE);
+        let warning = self.try_load_rom(rom)?;
+        self.reset();
+        Ok(warning)
     }
 
-    fn execute(&mut self, instruction: Chip8Instr) {
-        match instruction {
-            Chip8Instr::Clear => {
-                self.display = Self::DISPLAY_EMPTY;
-                if !self.options.hide_display {
-                    self.display();
-                }
-            }
-            Chip8Instr::Return => {
-                self.registers.pc = self.stack.pop().expect("return to be called after a call")
+    // Back to the power-on state with the RAM untouched: registers, stack, display and
+    // timers are cleared and PC is at the load address
+    pub fn reset(&mut self) {
+        self.registers = Registers {
+            pc: self.rom_start() as u16,
+            ..Registers::default()
+        };
+        self.stack.clear();
+        self.display = [[false; Self::DISPLAY_WIDTH]; Self::DISPLAY_HEIGHT];
+        self.plane2 = self.display;
+        self.planes = 1;
+        self.display_changed = true;
+        self.key_wait = None;
+        self.halted = false;
+        self.timers.modify(|t| *t = Timers::default());
+        self.clear_history();
+    }
+
+    pub fn read_mem(&self, addr: u16) -> Option<u8> {
+        self.ram.get(addr as usize).copied()
+    }
+
+    pub fn read_mem_slice(&self, start: u16, len: u16) -> Option<&[u8]> {
+        self.ram.get(start as usize..start as usize + len as usize)
+    }
+
+    // Decodes the RAM two bytes at a time from `start` up to `end` (excluded).
+    // This is a linear decode: data mixed with the code and instructions at odd
+    // addresses are not told apart.
+    pub fn instructions(
+        &self,
+        start: u16,
+        end: u16,
+    ) -> impl Iterator<Item = (u16, Chip8Instr)> + '_ {
+        let end = end.min(Self::RAM_SIZE as u16 - 1);
+        (start..end).step_by(2).map(|addr| {
+            let opcode = u16::from_be_bytes([self.ram[addr as usize], self.ram[addr as usize + 1]]);
+            (addr, Chip8Instr::from(opcode))
+        })
+    }
+
+    // Classic hexdump of `len` bytes from `start`, 16 per line with their ASCII
+    // representation. The range is clamped to the RAM.
+    pub fn dump_ram(&self, start: u16, len: u16) -> String {
+        let start = (start as usize).min(Self::RAM_SIZE);
+        let end = (start + len as usize).min(Self::RAM_SIZE);
+        let mut dump = String::new();
+        for (n, line) in self.ram[start..end].chunks(16).enumerate() {
+            let _ = write!(dump, "{:04x} ", start + 16 * n);
+            for byte in line {
+                let _ = write!(dump, " {byte:02x}");
             }
-            Chip8Instr::Jump(nnn) => self.registers.pc = nnn,
-            Chip8Instr::Call(nnn) => self.stack.push({
-                let tmp = self.registers.pc;
-                self.registers.pc = nnn;
-                tmp
-            }),
-            Chip8Instr::IfNE(x, nn) => {
+            // Keep the ASCII column aligned on the last line
+            dump.extend(core::iter::repeat_n("   ", 16 - line.len()));
+            let ascii: String = line
+                .iter()
+                .map(|&b| {
+                    if b.is_ascii_graphic() || b == b' ' {
+                        b as char
+                    } else {
+                        '.'
+                    }
+                })
+                .collect();
+            let _ = writeln!(dump, "  |{ascii}|");
+        }
+        dump
+    }
+
+    pub fn write_mem(&mut self, addr: u16, val: u8) -> Result<(), Chip8Error> {
+        let cell = self
+            .ram
+            .get_mut(addr as usize)
+            .ok_or(Chip8Error::OutOfBounds(addr as usize))?;
+        *cell = val;
+        self.ram_written(addr as usize, addr as usize + 1);
+        Ok(())
+    }
+
+    // Replaces the terminal output used by `run`
+    #[cfg(feature = "std")]
+    pub fn set_renderer(&mut self, renderer: Box<dyn Renderer + Send>) {
+        self.renderer = Some(renderer);
+    }
+
+    // Hook for frontends, invoked once per frame by `run` before rendering
+    #[cfg(feature = "std")]
+    pub fn set_on_frame(&mut self, on_frame: FrameCallback) {
+        self.on_frame = Some(on_frame);
+    }
+
+    // Lets the host's input handling drive the keypad: `run` calls `key_poll` once per
+    // frame and the key instructions read the returned keys until the next frame.
+    // It replaces the terminal keypad. The poll comes first in the frame, then `on_frame`
+    // which sees (and can still change) the polled keys.
+    #[cfg(feature = "std")]
+    pub fn set_key_poll(&mut self, key_poll: KeyPoll) {
+        self.key_poll = Some(key_poll);
+        self.terminal_input = None;
+    }
+
+    // Notifies the buzzer starts (as soon as FX18 runs) and stops (at the frame where the
+    // timer reaches 0), for audio backends playing the tone themselves
+    pub fn set_on_buzzer(&mut self, on_buzzer: BuzzerCallback) {
+        self.on_buzzer = Some(on_buzzer);
+    }
+
+    // Current value of the delay timer (read by FX07)
+    pub fn delay_timer(&self) -> u8 {
+        self.timers.get().delay
+    }
+
+    // Like FX15, also when the timers run on their thread: only the delay timer is
+    // written, a decrement of the buzzer happening meanwhile isn't lost
+    pub fn set_delay_timer(&mut self, value: u8) {
+        self.timers.modify(|t| t.delay = value);
+    }
+
+    pub fn buzzer_timer(&self) -> u8 {
+        self.timers.get().buzzer
+    }
+
+    // Like FX18, notifying `on_buzzer` when the buzzer starts or stops
+    pub fn set_buzzer_timer(&mut self, value: u8) {
+        self.timers.modify(|t| t.buzzer = value);
+        self.update_buzzer();
+    }
+
+    // Whether the buzzer sounds, i.e. the sound timer is above 0. Reads the timer shared
+    // with the timer thread atomically.
+    pub fn is_buzzing(&self) -> bool {
+        self.timers.get().buzzer > 0
+    }
+
+    fn update_buzzer(&mut self) {
+        let buzzing = self.is_buzzing();
+        if buzzing != self.buzzing {
+            self.buzzing = buzzing;
+            if let Some(on_buzzer) = &mut self.on_buzzer {
+                on_buzzer(buzzing);
+            }
+        }
+    }
+
+    // Handle to wait for the frames of `run` from another thread
+    #[cfg(feature = "std")]
+    pub fn frame_signal(&self) -> FrameSignal {
+        self.frame_signal.clone()
+    }
+
+    // Replaces the random source of the Rand instruction, e.g. with a seeded rng
+    // for reproducible runs
+    pub fn set_rng(&mut self, rng: Box<dyn RngCore + Send>) {
+        self.rng = Some(rng);
+    }
+
+    // Stable fingerprint of the display, for golden tests
+    pub fn display_hash(&self) -> u64 {
+        hash_display(&self.display)
+    }
+
+    // The display as a 1-bit bitmap, see `pack_display`
+    pub fn display_packed(&self) -> Vec<u8> {
+        pack_display(&self.display)
+    }
+
+    pub fn set_key(&mut self, key: u8, pressed: bool) -> Result<(), Chip8Error> {
+        if key >= 16 {
+            return Err(Chip8Error::InvalidKey(key));
+        }
+        if pressed {
+            self.keypad.press(key);
+        } else {
+            self.keypad.release(key);
+        }
+        Ok(())
+    }
+
+    // Out of range keys are never pressed
+    pub fn key_state(&self, key: u8) -> bool {
+        self.keypad.is_pressed(key)
+    }
+
+    // Whole keypad as a bitmask, bit n set when key n is pressed
+    pub fn keypad_state(&self) -> u16 {
+        self.keypad.bits()
+    }
+
+    pub fn set_keypad_state(&mut self, bits: u16) {
+        self.keypad = Keypad::from_bits(bits);
+    }
+
+    // Keys held down, for hosts feeding their own input
+    pub fn keypad_mut(&mut self) -> &mut Keypad {
+        &mut self.keypad
+    }
+
+    #[cfg(feature = "std")]
+    pub fn pre_run(&mut self) {
+        self.frame_signal.set_stopped(false);
+        if self.options.terminal_keypad && self.terminal_input.is_none() && self.key_poll.is_none()
+        {
+            self.terminal_input = Some(TerminalInput::new(
+                self.options
+                    .key_hold_timeout
+                    .unwrap_or(TerminalInput::DEFAULT_HOLD_TIMEOUT),
+            ));
+        }
+        self.timers.start();
+    }
+
+    // Stops `run` from executing instructions and freezes the timers until `resume`.
+    // Single steps with `run_once` are still possible.
+    pub fn pause(&mut self) {
+        self.paused = true;
+        #[cfg(feature = "std")]
+        self.timers.set_paused(true);
+    }
+
+    pub fn resume(&mut self) {
+        self.paused = false;
+        #[cfg(feature = "std")]
+        self.timers.set_paused(false);
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    // Runs the instructions as fast as possible while the timers and rendering stay at
+    // 60Hz, to fast-forward. Back at `freq` (from the current time) once disabled.
+    pub fn set_turbo(&mut self, turbo: bool) {
+        self.turbo = turbo;
+    }
+
+    pub fn is_turbo(&self) -> bool {
+        self.turbo
+    }
+
+    // The ROM ended with a jump to itself, with the `halt_on_self_jump` option
+    pub fn is_halted(&self) -> bool {
+        self.halted
+    }
+
+    // Decrements the delay and buzzer timers, the host is expected to call it at 60Hz
+    // when `manual_timers` is set (or without std).
+    // Only marks the start of a frame (see `display_wait`) when the timers are driven by
+    // the background thread.
+    pub fn tick_timers(&mut self) {
+        #[cfg(feature = "std")]
+        self.record_frame();
+        if !self.paused {
+            self.timers.tick();
+        }
+        self.update_buzzer();
+        self.vblank = true;
+        self.frames += 1;
+    }
+
+    // Events recorded since the last call, oldest first (empty without `record_events`).
+    // They accumulate until taken.
+    pub fn take_events(&mut self) -> Vec<VmEvent> {
+        core::mem::take(&mut self.events)
+    }
+
+    #[cfg(not(feature = "catch_panics"))]
+    pub fn run_once(&mut self) -> Result<(), Chip8Error> {
+        self.run_instruction()
+    }
+
+    // With the `catch_panics` feature, a panic of the emulator while running the
+    // instruction is returned as `Chip8Error::InternalPanic` instead of unwinding into
+    // the frontend, and PC is put back on that instruction.
+    // This is a safety net for bugs of the emulator, not a way to handle errors: the
+    // instruction may have partially run, so the state should only be inspected.
+    #[cfg(feature = "catch_panics")]
+    pub fn run_once(&mut self) -> Result<(), Chip8Error> {
+        let pc = self.registers.pc;
+        let opcode = u16::from_be_bytes([
+            self.ram.get(pc as usize).copied().unwrap_or(0),
+            self.ram.get(pc as usize + 1).copied().unwrap_or(0),
+        ]);
+        let result =
+            std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| self.run_instruction()));
+        result.unwrap_or_else(|_| {
+            self.registers.pc = pc;
+            Err(Chip8Error::InternalPanic(pc as usize, opcode))
+        })
+    }
+
+    fn run_instruction(&mut self) -> Result<(), Chip8Error> {
+        if !self.registers.pc.is_multiple_of(2) {
+            if self.options.align_pc {
+                self.registers.pc &= !1;
+            } else if !self.unaligned_fetch {
+                self.unaligned_fetch = true;
+                self.warnln(&format!("unaligned fetch at {:#05x}", self.registers.pc));
+            }
+        }
+        if self.options.strict {
+            self.check_invariants()?;
+        }
+        // Only reachable with an odd PC
+        if self.registers.pc as usize + 1 >= Self::RAM_SIZE {
+            return Err(Chip8Error::OutOfBounds(Self::RAM_SIZE));
+        }
+        #[cfg(feature = "std")]
+        self.record_keys();
+        let opcode = self.fetch_instruction();
+        #[cfg(feature = "std")]
+        self.write_trace(opcode);
+
+        let instruction = self.decode(opcode);
+        self.debugln(&format!("input (raw,decoded): {opcode:x},{instruction:?}"));
+        if self.options.strict {
+            for reg in instruction.registers() {
+                if reg > 0xF {
+                    return Err(Chip8Error::BrokenInvariant(
+                        "register index above 0xF",
+                        reg as usize,
+                    ));
+                }
+            }
+        }
+        if instruction == Chip8Instr::Unknown && !self.options.skip_unknown {
+            return Err(Chip8Error::UnknownOpcode(opcode));
+        }
+        if self.options.history_depth > 0 {
+            self.record_history(&instruction);
+        }
+        self.cycles += 1;
+        #[cfg(feature = "std")]
+        if self.options.opcode_stats {
+            *self.opcode_counts.entry(instruction.name()).or_default() += 1;
+            *self.pc_counts.entry(self.registers.pc).or_default() += 1;
+        }
+        self.incr_pc()?;
+        if instruction == Chip8Instr::Unknown && self.options.skip_unknown {
+            let pc = self.instruction_addr();
+            self.warnln(&format!(
+                "skipped unknown opcode {opcode:#06x} at {pc:#05x}"
+            ));
+            if self.options.record_events {
+                self.events.push(VmEvent::UnknownOpcode {
+                    frame: self.frames,
+                    pc,
+                    opcode,
+                });
+            }
+            return Ok(());
+        }
+        self.execute(instruction)?;
+        if self.options.strict {
+            self.check_invariants()?;
+        }
+        self.debugln(&format!("{self:?}"));
+        Ok(())
+    }
+
+    // PC is even and leaves room for a whole instruction, I is inside the RAM and the
+    // stack is at most `STACK_LIMIT` deep.
+    // Checked before and after each instruction with the `strict` option, so a broken
+    // state is reported by the instruction causing it instead of panicking later.
+    pub fn check_invariants(&self) -> Result<(), Chip8Error> {
+        let pc = self.registers.pc as usize;
+        if !pc.is_multiple_of(2) {
+            return Err(Chip8Error::BrokenInvariant("PC is odd", pc));
+        }
+        if pc + 1 >= Self::RAM_SIZE {
+            return Err(Chip8Error::BrokenInvariant(
+                "PC past the end of the RAM",
+                pc,
+            ));
+        }
+        let i = self.registers.i as usize;
+        if i >= Self::RAM_SIZE {
+            return Err(Chip8Error::BrokenInvariant("I outside of the RAM", i));
+        }
+        if self.stack.len() > Self::STACK_LIMIT {
+            return Err(Chip8Error::BrokenInvariant(
+                "stack deeper than STACK_LIMIT",
+                self.stack.len(),
+            ));
+        }
+        Ok(())
+    }
+
+    // Runs the next instruction, or the whole subroutine if it is a call, stopping at the
+    // instruction after it. Gives up (returning false) after `STEP_OVER_LIMIT` instructions
+    // in case the subroutine never returns.
+    pub fn step_over(&mut self) -> Result<bool, Chip8Error> {
+        let depth = self.stack_depth();
+        let is_call = matches!(self.peek_instruction().1, Chip8Instr::Call(_));
+        self.run_once()?;
+        if !is_call {
+            return Ok(true);
+        }
+        for _ in 0..Self::STEP_OVER_LIMIT {
+            if self.stack_depth() <= depth {
+                return Ok(true);
+            }
+            self.run_once()?;
+        }
+        Ok(self.stack_depth() <= depth)
+    }
+
+    // Runs `n` instructions, stopping early when PC lands on a breakpoint, the VM halts or
+    // an instruction fails. The first instruction is run even on a breakpoint.
+    pub fn step(&mut self, n: usize) -> StopReason {
+        for _ in 0..n {
+            if let Some(reason) = self.debug_step() {
+                return reason;
+            }
+        }
+        StopReason::Count
+    }
+
+    // Runs until PC is `addr` (after at least one instruction), a breakpoint, a halt or an
+    // error. Gives up after `STEP_OVER_LIMIT` instructions.
+    pub fn continue_to(&mut self, addr: u16) -> StopReason {
+        for _ in 0..Self::STEP_OVER_LIMIT {
+            if let Some(reason) = self.debug_step() {
+                return reason;
+            }
+            if self.registers.pc == addr {
+                return StopReason::Reached;
+            }
+        }
+        StopReason::Count
+    }
+
+    fn debug_step(&mut self) -> Option<StopReason> {
+        if let Err(e) = self.run_once() {
+            return Some(StopReason::Error(e));
+        }
+        let pc = self.registers.pc;
+        if self.halted {
+            Some(StopReason::Halted)
+        } else if self.breakpoints.contains(&pc) {
+            Some(StopReason::Breakpoint(pc))
+        } else {
+            None
+        }
+    }
+
+    pub fn add_breakpoint(&mut self, addr: u16) {
+        self.breakpoints.insert(addr);
+    }
+
+    // Whether there was a breakpoint at `addr`
+    pub fn remove_breakpoint(&mut self, addr: u16) -> bool {
+        self.breakpoints.remove(&addr)
+    }
+
+    pub fn clear_breakpoints(&mut self) {
+        self.breakpoints.clear();
+    }
+
+    pub fn breakpoints(&self) -> &BTreeSet<u16> {
+        &self.breakpoints
+    }
+
+    // Opcode at PC and its decoding, the instruction `run_once` will execute next
+    pub fn peek_instruction(&self) -> (u16, Chip8Instr) {
+        let opcode = self.fetch_instruction();
+        (opcode, Chip8Instr::from(opcode))
+    }
+
+    // Source address, kind and target of every jump and call decoded in the RAM from
+    // `start` (included) to `end` (excluded), 2 bytes at a time from `start`.
+    // Data decoding as an instruction is reported too, the scan doesn't follow the flow.
+    pub fn control_targets(&self, start: u16, end: u16) -> Vec<(u16, TargetKind, u16)> {
+        let end = (end as usize).min(Self::RAM_SIZE - 1);
+        (start as usize..end)
+            .step_by(2)
+            .filter_map(|addr| {
+                let opcode = u16::from_be_bytes([self.ram[addr], self.ram[addr + 1]]);
+                let (kind, target) = match Chip8Instr::from(opcode) {
+                    Chip8Instr::Jump(nnn) => (TargetKind::Jump, nnn),
+                    Chip8Instr::Call(nnn) => (TargetKind::Call, nnn),
+                    Chip8Instr::JumpOff(nnn) => (TargetKind::Dynamic, nnn),
+                    _ => return None,
+                };
+                Some((addr as u16, kind, target))
+            })
+            .collect()
+    }
+
+    // Layout of the RAM in address order, covering it entirely. Empty regions are left out
+    // (e.g. the ROM before one is loaded).
+    pub fn memory_map(&self) -> Vec<MemRegion> {
+        let rom_start = self.rom_start();
+        let bounds = [
+            (MemRegionKind::Reserved, 0, Self::FONT_START),
+            (MemRegionKind::Font, Self::FONT_START, Self::BIG_FONT_START),
+            (
+                MemRegionKind::BigFont,
+                Self::BIG_FONT_START,
+                Self::BIG_FONT_START + Self::BIG_FONT_SIZE,
+            ),
+            (
+                MemRegionKind::Reserved,
+                Self::BIG_FONT_START + Self::BIG_FONT_SIZE,
+                rom_start,
+            ),
+            (MemRegionKind::Rom, rom_start, rom_start + self.rom_len),
+            (
+                MemRegionKind::Free,
+                rom_start + self.rom_len,
+                Self::RAM_SIZE,
+            ),
+        ];
+        bounds
+            .into_iter()
+            .filter(|&(_, start, end)| start < end)
+            .map(|(kind, start, end)| MemRegion {
+                kind,
+                start: start as u16,
+                end: end as u16,
+            })
+            .collect()
+    }
+
+    // Return addresses of the pending calls, the innermost last
+    pub fn call_stack(&self) -> &[u16] {
+        &self.stack
+    }
+
+    pub fn stack_depth(&self) -> usize {
+        self.stack.len()
+    }
+
+    // One line view of the registers and timers, for logging every frame or instruction
+    // e.g. `PC=0x21a I=0x2ea V0=00 V1=3f ... VF=01 DT=05 ST=00 SP=2`
+    pub fn state_summary(&self) -> String {
+        let mut summary = format!("PC={:#05x} I={:#05x}", self.registers.pc, self.registers.i);
+        for reg in 0..16 {
+            let _ = write!(summary, " V{reg:X}={:02x}", self.registers.get(reg));
+        }
+        let timers = self.timers.get();
+        let _ = write!(
+            summary,
+            " DT={:02x} ST={:02x} SP={}",
+            timers.delay,
+            timers.buzzer,
+            self.stack.len()
+        );
+        summary
+    }
+
+    // Clock speed actually used, `freq` clamped to `MIN_FREQ..=MAX_FREQ`
+    pub fn freq(&self) -> u32 {
+        self.freq.clamp(Self::MIN_FREQ, Self::MAX_FREQ)
+    }
+
+    // Changes the clock speed, also while running
+    pub fn set_freq(&mut self, hz: u32) -> Result<(), Chip8Error> {
+        if !(Self::MIN_FREQ..=Self::MAX_FREQ).contains(&hz) {
+            return Err(Chip8Error::InvalidFrequency(hz));
+        }
+        self.freq = hz;
+        Ok(())
+    }
+
+    // Number of instructions executed since the VM was created
+    pub fn cycles(&self) -> u64 {
+        self.cycles
+    }
+
+    // The I register
+    pub fn index(&self) -> U12 {
+        self.registers.i
+    }
+
+    // Sets I, masked to 12 bits with `wrap_addresses`, else `Chip8Error::AddressOverflow`
+    // past 0xFFF like `IncrI`
+    pub fn set_index(&mut self, i: u16) -> Result<(), Chip8Error> {
+        self.registers.i = self.checked_addr(i as usize)?;
+        Ok(())
+    }
+
+    // Executions per instruction kind, only recorded with the `opcode_stats` option
+    #[cfg(feature = "std")]
+    pub fn opcode_stats(&self) -> HashMap<&'static str, u64> {
+        self.opcode_counts.clone()
+    }
+
+    // Runs until an instruction fails
+    #[cfg(feature = "std")]
+    pub fn run(&mut self) -> Result<(), Chip8Error> {
+        self.pre_run();
+        let result = match self.options.timing {
+            TimingModel::Frequency => self.run_frequency(),
+            TimingModel::CyclesPerFrame(cycles) => self.run_per_frame(cycles),
+        };
+        // Wake up the threads waiting for a frame that won't come
+        self.frame_signal.set_stopped(true);
+        result
+    }
+
+    #[cfg(feature = "std")]
+    fn run_frequency(&mut self) -> Result<(), Chip8Error> {
+        let mut pacer = Pacer::new();
+        let mut frames = Pacer::new();
+        // Slept until this frame on an idle loop, which must now run once to see the
+        // new timers and keys
+        let mut woken = false;
+        loop {
+            if self.paused {
+                // Keep rendering and polling input, at the frame rate only
+                frames.wait(Timers::TIMER_FREQ);
+                self.frame();
+                continue;
+            }
+            if self.options.idle_sleep && !woken && self.is_idle_loop() {
+                frames.wait(Timers::TIMER_FREQ);
+                self.frame();
+                // Don't run the skipped instructions to catch up
+                pacer = Pacer::new();
+                woken = true;
+                continue;
+            }
+            self.run_once()?;
+            woken = false;
+            if self.halted {
+                // Show the final screen
+                self.frame();
+                return Ok(());
+            }
+            // Instructions run at `freq`, timers and rendering at 60Hz
+            if frames.due(Timers::TIMER_FREQ) {
+                self.frame();
+            }
+            if self.turbo {
+                // Restart from now once turbo is off, instead of catching up
+                pacer = Pacer::new();
+            } else {
+                pacer.wait(self.freq());
+            }
+        }
+    }
+
+    #[cfg(feature = "std")]
+    fn run_per_frame(&mut self, cycles: u32) -> Result<(), Chip8Error> {
+        let mut frames = Pacer::new();
+        // See `run`
+        let mut woken = false;
+        loop {
+            let mut executed = 0;
+            // In turbo, until the end of the frame
+            let end = frames.next(Timers::TIMER_FREQ);
+            while !self.paused && (executed < cycles || self.turbo && Instant::now() < end) {
+                if self.options.idle_sleep && !woken && self.is_idle_loop() {
+                    woken = true;
+                    break;
+                }
+                self.run_once()?;
+                woken = false;
+                executed += 1;
+                if self.halted {
+                    self.frame();
+                    return Ok(());
+                }
+            }
+            self.frame();
+            frames.wait(Timers::TIMER_FREQ);
+        }
+    }
+
+    // Average number of instructions to run per 60Hz frame under the `timing` option,
+    // for hosts driving the frames themselves
+    pub fn instructions_per_frame(&self) -> f64 {
+        match self.options.timing {
+            TimingModel::Frequency => self.freq() as f64 / Timers::TIMER_FREQ as f64,
+            TimingModel::CyclesPerFrame(cycles) => cycles as f64,
+        }
+    }
+
+    // Whether PC is on the backward jump of a short loop which only reads the delay timer
+    // and the keys, e.g. `loop: LD V0, DT ; SE V0, 0 ; JP loop`.
+    // Until the timers or keys change, each iteration does exactly the same thing.
+    #[cfg(feature = "std")]
+    fn is_idle_loop(&self) -> bool {
+        const MAX_BODY: usize = 8;
+        let pc = self.registers.pc as usize;
+        let (_, Chip8Instr::Jump(start)) = self.peek_instruction() else {
+            return false;
+        };
+        let start = start as usize;
+        if start > pc || pc - start > 2 * MAX_BODY {
+            return false;
+        }
+        self.instructions(start as u16, pc as u16)
+            .all(|(_, instr)| {
+                matches!(
+                    instr,
+                    Chip8Instr::GetDelay(_)
+                        | Chip8Instr::SkipEqImm(..)
+                        | Chip8Instr::SkipNeImm(..)
+                        | Chip8Instr::SkipEqReg(..)
+                        | Chip8Instr::SkipNeReg(..)
+                        | Chip8Instr::KeyDown(_)
+                        | Chip8Instr::KeyUp(_)
+                )
+            })
+    }
+
+    #[cfg(feature = "std")]
+    fn frame(&mut self) {
+        self.tick_timers();
+        if let Some(key_poll) = &mut self.key_poll {
+            self.keypad = Keypad::from_bits(key_poll());
+        } else if let Some(input) = &mut self.terminal_input {
+            input.poll(&mut self.keypad);
+        }
+        if let Some(on_frame) = &mut self.on_frame {
+            on_frame(&self.display, &mut self.keypad);
+        }
+        if self.display_changed || self.fading {
+            self.present_frame();
+        }
+        self.frame_signal.notify(self.frames);
+    }
+
+    // Copies the back buffer to the front one and renders it. `run` does it at the end
+    // of each frame where the display changed, debuggers can call it to show a frame
+    // being drawn.
+    // The renderer is called at most 60 times per second, the presents coming sooner
+    // only update the front buffer: the frame stays pending (as if the display changed)
+    // until the next render, by `present` or the next frame of `run`. The phosphor fade
+    // advances once per render.
+    pub fn present(&mut self) {
+        #[cfg(feature = "std")]
+        {
+            let due = self
+                .last_render
+                .is_none_or(|last| last.elapsed().as_secs_f64() >= 1. / Timers::TIMER_FREQ as f64);
+            if !due {
+                self.front = self.display;
+                self.display_changed = true;
+                return;
+            }
+        }
+        self.present_frame();
+    }
+
+    // `present` without the rate limit, for the loops already running at 60Hz
+    pub(crate) fn present_frame(&mut self) {
+        self.front = self.display;
+        self.display_changed = false;
+        self.update_intensity();
+        #[cfg(feature = "std")]
+        if let Some(renderer) = &mut self.renderer {
+            renderer.set_status(&format!(
+                "PC={:#05x} cycles={}",
+                self.registers.pc, self.cycles
+            ));
+            renderer.render_rows(&self.front, &self.intensity, self.dirty_rows);
+            self.last_render = Some(Instant::now());
+        }
+    }
+
+    // Rows which changed on screen (pixels or shades) with the last rendered `present`,
+    // bit n for row n
+    pub fn dirty_rows(&self) -> u32 {
+        self.dirty_rows
+    }
+
+    // Plain text copy of the drawing display for golden files: 32 lines of 64 characters,
+    // `#` for the pixels on and `.` for the ones off
+    pub fn display_to_string(&self) -> String {
+        let mut text = String::with_capacity((Self::DISPLAY_WIDTH + 1) * Self::DISPLAY_HEIGHT);
+        for row in &self.display {
+            text.extend(row.iter().map(|&on| if on { '#' } else { '.' }));
+            text.push('\n');
+        }
+        text
+    }
+
+    // Inverse of `display_to_string`, the display is left untouched if `text` isn't valid.
+    // The trailing newline is optional.
+    pub fn display_from_string(&mut self, text: &str) -> Result<(), Chip8Error> {
+        let mut display = [[false; Self::DISPLAY_WIDTH]; Self::DISPLAY_HEIGHT];
+        let mut lines = text.lines();
+        for row in &mut display {
+            let line = lines
+                .next()
+                .ok_or(Chip8Error::InvalidState("display text has too few lines"))?;
+            if line.chars().count() != Self::DISPLAY_WIDTH {
+                return Err(Chip8Error::InvalidState("display line of the wrong width"));
+            }
+            for (pixel, c) in row.iter_mut().zip(line.chars()) {
+                *pixel = match c {
+                    '#' => true,
+                    '.' => false,
+                    _ => return Err(Chip8Error::InvalidState("display pixel not '#' or '.'")),
+                };
+            }
+        }
+        if lines.next().is_some() {
+            return Err(Chip8Error::InvalidState("display text has too many lines"));
+        }
+        self.display = display;
+        self.display_changed = true;
+        Ok(())
+    }
+
+    // The last presented frame
+    pub fn front_display(&self) -> &Display {
+        &self.front
+    }
+
+    // Brightness of the pixels of the last presented frame
+    pub fn intensity(&self) -> &Intensity {
+        &self.intensity
+    }
+
+    fn update_intensity(&mut self) {
+        let step = match self.options.phosphor_frames {
+            0 => u8::MAX,
+            frames => u8::MAX.div_ceil(frames),
+        };
+        self.fading = false;
+        // A pixel turned on or off always changes its intensity
+        self.dirty_rows = 0;
+        for (y, (pixels, levels)) in self.front.iter().zip(self.intensity.iter_mut()).enumerate() {
+            for (&on, level) in pixels.iter().zip(levels.iter_mut()) {
+                let previous = *level;
+                *level = if on {
+                    u8::MAX
+                } else {
+                    level.saturating_sub(step)
+                };
+                self.fading |= !on && *level > 0;
+                self.dirty_rows |= ((*level != previous) as u32) << y;
+            }
+        }
+    }
+
+    fn execute(&mut self, instruction: Chip8Instr) -> Result<(), Chip8Error> {
+        match instruction {
+            Chip8Instr::Sys(nnn) => {
+                // No machine code to run, modern interpreters skip it too
+                self.debugln(&format!("ignored machine code call to {nnn:#05x}"));
+            }
+            Chip8Instr::Clear => {
+                if self.planes & 1 != 0 {
+                    self.display = Self::DISPLAY_EMPTY;
+                }
+                if self.planes & 2 != 0 {
+                    self.plane2 = Self::DISPLAY_EMPTY;
+                }
+                self.display_changed = true;
+                if self.options.record_events {
+                    self.events.push(VmEvent::Clear {
+                        frame: self.frames,
+                        pc: self.instruction_addr(),
+                    });
+                }
+            }
+            Chip8Instr::Return => {
+                let Some(addr) = self.stack.pop() else {
+                    return Err(Chip8Error::StackUnderflow(self.instruction_addr() as usize));
+                };
+                self.registers.pc = addr;
+            }
+            Chip8Instr::Jump(nnn) => {
+                // PC was already moved past the jump
+                if self.options.halt_on_self_jump && nnn as usize + 2 == self.registers.pc as usize
+                {
+                    self.halted = true;
+                }
+                self.registers.pc = nnn
+            }
+            Chip8Instr::Call(nnn) => {
+                // PC was already incremented by `run_once`: this is the return address
+                self.stack.push(self.registers.pc);
+                self.registers.pc = nnn;
+            }
+            Chip8Instr::SkipEqImm(x, nn) => {
                 if self.registers.get(x) == nn {
-                    self.incr_pc();
+                    self.incr_pc()?;
                 }
             }
-            Chip8Instr::IfE(x, nn) => {
+            Chip8Instr::SkipNeImm(x, nn) => {
                 if self.registers.get(x) != nn {
-                    self.incr_pc();
+                    self.incr_pc()?;
                 }
             }
-            Chip8Instr::IfRNE(x, y) => {
+            Chip8Instr::SkipEqReg(x, y) => {
                 if self.registers.get(x) == self.registers.get(y) {
-                    self.incr_pc();
+                    self.incr_pc()?;
                 }
             }
             Chip8Instr::Set(vx, nn) => self.registers.set(vx, nn),
@@ -474,6 +2445,9 @@ impl Chip8VM {
                     _ => panic!("Oopsy"),
                 };
                 self.registers.set(x, r);
+                if self.options.vf_reset {
+                    self.registers.set(15, 0);
+                }
             }
             Chip8Instr::ArithmOp(x, y, op) => {
                 let (r, mut o) = match op {
@@ -485,193 +2459,575 @@ impl Chip8VM {
                 if op == 5 || op == 7 {
                     o = !o;
                 }
-                self.registers.set(15, o as u8);
+                // VF written last so the flag wins when x is VF
                 self.registers.set(x, r);
+                self.registers.set(15, o as u8);
             }
             Chip8Instr::ShiftOp(x, y, op) => {
-                if self.options.old_shift {
-                    self.registers.set(x, self.registers.get(y))
-                }
-                let v = self.registers.get(x);
+                let v = if self.options.old_shift {
+                    self.registers.get(y)
+                } else {
+                    self.registers.get(x)
+                };
                 let (r, b) = match op {
                     6 => ((v & (0xFE)) >> 1, v & 1 == 1),
                     0xE => ((v & (0x7F)) << 1, v & 128 != 0),
                     _ => panic!("Oopsy"),
                 };
+                // VF written last so the shifted out bit wins when x is VF
                 self.registers.set(x, r);
                 self.registers.set(15, b as u8);
             }
-            Chip8Instr::IfRE(x, y) => {
+            Chip8Instr::SkipNeReg(x, y) => {
                 if self.registers.get(x) != self.registers.get(y) {
-                    self.incr_pc();
+                    self.incr_pc()?;
                 }
             }
             Chip8Instr::SetI(nnn) => self.registers.i = nnn,
             Chip8Instr::JumpOff(nnn) => {
-                if self.options.new_jump_off {
-                    self.registers.pc = nnn + self.registers.get((nnn >> 8) as u8) as U12;
+                let offset = if self.options.new_jump_off {
+                    self.registers.get((nnn >> 8) as u8)
                 } else {
-                    self.registers.pc = nnn + self.registers.get(0) as U12;
-                }
+                    self.registers.get(0)
+                };
+                self.registers.pc = self.checked_addr(nnn as usize + offset as usize)?;
             }
             Chip8Instr::Rand(x, nn) => {
-                let rand: u8 = rand::random();
+                let rand: u8 = match &mut self.rng {
+                    Some(rng) => rng.gen(),
+                    #[cfg(feature = "std")]
+                    None => rand::random(),
+                    #[cfg(not(feature = "std"))]
+                    None => unreachable!("a rng is always set without std"),
+                };
                 self.registers.set(x, nn & rand)
             }
+            Chip8Instr::Display(_, _, _) if self.options.display_wait && !self.vblank => {
+                // Drawing waits for the start of the next frame
+                self.rewind_pc();
+            }
             Chip8Instr::Display(vx, vy, n) => {
-                let x = self.registers.get(vx) % (Self::DISPLAY_WIDTH as u8);
-                let y = self.registers.get(vy) % (Self::DISPLAY_HEIGHT as u8);
-                let sprite_addr = self.registers.i;
-                let sprite_height = n;
-                self.draw_sprite(x, y, sprite_addr, sprite_height);
-                if !self.options.hide_display {
-                    self.display();
+                // A sprite reaching past the end of the RAM fails like the other memory
+                // instructions, or its missing rows are blank with `wrap_addresses`
+                if !self.options.wrap_addresses {
+                    let len = n as usize * self.planes.count_ones() as usize;
+                    self.check_range(self.registers.i as usize, len)?;
+                }
+                self.vblank = false;
+                let (x, y) = (self.registers.get(vx), self.registers.get(vy));
+                self.draw(x, y, self.registers.i, n);
+                if self.options.record_events && self.registers.get(0xF) == 1 {
+                    self.events.push(VmEvent::Collision {
+                        frame: self.frames,
+                        pc: self.instruction_addr(),
+                        x: x % (Self::DISPLAY_WIDTH as u8),
+                        y: y % (Self::DISPLAY_HEIGHT as u8),
+                    });
                 }
             }
-            Chip8Instr::KeyUp(_x) => {
-                println!("KeyUp")
+            Chip8Instr::KeyUp(x) => {
+                if !self.keypad.is_pressed(self.registers.get(x) & 0xF) {
+                    self.incr_pc()?;
+                }
             }
-            Chip8Instr::KeyDown(_x) => {
-                println!("KeyDown");
+            Chip8Instr::KeyDown(x) => {
+                if self.keypad.is_pressed(self.registers.get(x) & 0xF) {
+                    self.incr_pc()?;
+                }
             }
             Chip8Instr::GetDelay(x) => {
-                println!("Delay");
-                self.registers
-                    .set(x, self.timers.timers.lock().unwrap().delay);
+                self.debugln("Delay");
+                self.registers.set(x, self.timers.get().delay);
             }
             Chip8Instr::GetKey(x) => {
-                let mut buf = String::new();
-                std::io::stdin()
-                    .read_line(&mut buf)
-                    .expect("Failed to read line");
-                let key = buf.chars().next().expect("input");
-
-                let key = match key {
-                    k if k.is_ascii_digit() => k as u8 - b'0',
-                    k if k.is_ascii_lowercase() => k as u8 - b'a' + 10,
-                    k if k.is_ascii_uppercase() => k as u8 - b'A' + 10,
-                    _ => 16,
-                };
-                if key < 16 {
-                    self.registers.set(x, key & 0xF);
+                // Like the COSMAC VIP, waits for a key to be pressed then released.
+                // The instruction is fetched again until then so the timers and input
+                // polling keep running in the meantime.
+                match self.key_wait {
+                    Some(key) if !self.keypad.is_pressed(key) => {
+                        self.registers.set(x, key);
+                        self.key_wait = None;
+                    }
+                    Some(_) => self.rewind_pc(),
+                    None => {
+                        self.key_wait = (0..16).find(|&k| self.keypad.is_pressed(k));
+                        self.rewind_pc();
+                    }
                 }
             }
-            Chip8Instr::SetDelay(x) => {
-                self.timers.timers.lock().unwrap().delay = self.registers.get(x)
-            }
-            Chip8Instr::SetBuzzer(x) => {
-                self.timers.timers.lock().unwrap().buzzer = self.registers.get(x)
+            Chip8Instr::SetDelay(x) => self.set_delay_timer(self.registers.get(x)),
+            Chip8Instr::SetBuzzer(x) => self.set_buzzer_timer(self.registers.get(x)),
+            Chip8Instr::IncrI(x) => {
+                self.registers.i =
+                    self.checked_addr(self.registers.i as usize + self.registers.get(x) as usize)?
             }
-            Chip8Instr::IncrI(x) => self.registers.i += self.registers.get(x) as u16,
             Chip8Instr::Char(x) => self.registers.i = self.char_index(self.registers.get(x)),
+            Chip8Instr::BigChar(x) => self.registers.i = self.big_char_index(self.registers.get(x)),
             Chip8Instr::Decimal(x) => {
+                self.check_range(self.registers.i as usize, 3)?;
+                self.check_writable(self.registers.i as usize)?;
                 let x = self.registers.get(x);
                 self.ram[self.registers.i as usize] = x / 100;
                 self.ram[self.registers.i as usize + 1] = (x % 100) / 10;
                 self.ram[self.registers.i as usize + 2] = x % 10;
+                let i = self.registers.i as usize;
+                self.ram_written(i, i + 3);
             }
             Chip8Instr::Save(x) => {
+                self.check_range(self.registers.i as usize, x as usize + 1)?;
+                self.check_writable(self.registers.i as usize)?;
                 for i in 0..=x {
                     self.ram[self.registers.i as usize + i as usize] = self.registers.get(i);
                 }
-                if self.options.incr_i_when_mem {
-                    self.registers.i += x as u16;
-                }
+                let i = self.registers.i as usize;
+                self.ram_written(i, i + x as usize + 1);
+                self.incr_i_after_mem(x)?;
             }
             Chip8Instr::Load(x) => {
+                self.check_range(self.registers.i as usize, x as usize + 1)?;
                 for i in 0..=x {
                     self.registers
                         .set(i, self.ram[self.registers.i as usize + i as usize]);
                 }
-                if self.options.incr_i_when_mem {
-                    self.registers.i += x as u16;
-                }
+                self.incr_i_after_mem(x)?;
             }
-            _ => panic!("Not implemented ({:?})", instruction),
+            Chip8Instr::Unknown => unreachable!("unknown opcodes don't get executed"),
         }
+        Ok(())
     }
 
-    fn fetch_instruction(&self) -> u16 {
-        let first_byte = self.ram[self.registers.pc as usize];
-        let second_byte = self.ram[(self.registers.pc + 1) as usize];
+    // I after FX55 and FX65, see `incr_i_when_mem`
+    fn incr_i_after_mem(&mut self, x: U4) -> Result<(), Chip8Error> {
+        if self.options.incr_i_when_mem {
+            let step = x as usize + self.options.incr_i_past_last as usize;
+            self.registers.i = self.checked_addr(self.registers.i as usize + step)?;
+        }
+        Ok(())
+    }
+
+    // The second byte reads as 0 past the end of the RAM (`run_once` fails there)
+    fn fetch_instruction(&self) -> u16 {
+        let first_byte = self.ram[self.registers.pc as usize];
+        let second_byte = self
+            .ram
+            .get(self.registers.pc as usize + 1)
+            .copied()
+            .unwrap_or(0);
         u16::from_be_bytes([first_byte, second_byte])
     }
 
-    fn display(&self) {
-        thread::sleep(Duration::from_secs_f64(1_f64 / 60_f64));
-        if !self.options.keep_display {
-            print!("{esc}c", esc = 27 as char);
+    // Draws the `height` bytes sprite at `sprite_addr` like DXYN, with the start
+    // coordinates wrapped to the screen (or nothing drawn off screen with
+    // `clip_sprite_start`) and the sprite clipped or wrapped at the edges
+    // (`wrap_sprites`). VF is set to 1 if any pixel was turned off, the returned value
+    // is how many were. Rows past the end of the RAM are blank.
+    // With both planes selected, the sprite of the second one follows in RAM.
+    pub fn draw(&mut self, x: u8, y: u8, sprite_addr: u16, height: u8) -> u32 {
+        let off_screen = x as usize >= Self::DISPLAY_WIDTH || y as usize >= Self::DISPLAY_HEIGHT;
+        if self.options.clip_sprite_start && off_screen {
+            self.registers.set(15, 0);
+            return 0;
         }
-        for y in 0..Self::DISPLAY_HEIGHT {
-            for x in 0..Self::DISPLAY_WIDTH {
-                if self.display[y][x] {
-                    print!("⬜");
-                } else {
-                    print!("⬛");
-                }
-            }
-            println!();
+        let x = x % (Self::DISPLAY_WIDTH as u8);
+        let y = y % (Self::DISPLAY_HEIGHT as u8);
+        let erased = self.draw_sprite(x, y, sprite_addr, height, self.planes);
+        self.display_changed = true;
+        erased
+    }
+
+    // XO-CHIP planes the drawing instructions use (and 00E0 clears): bit 0 for the first
+    // plane (`display`, the only one of CHIP-8), bit 1 for the second. 0 disables drawing.
+    pub fn select_planes(&mut self, planes: u8) {
+        self.planes = planes & 0b11;
+    }
+
+    pub fn selected_planes(&self) -> u8 {
+        self.planes
+    }
+
+    // Pixels of the XO-CHIP plane 0 (`display`) or 1
+    pub fn plane(&self, plane: usize) -> Option<&Display> {
+        match plane {
+            0 => Some(&self.display),
+            1 => Some(&self.plane2),
+            _ => None,
         }
     }
 
-    fn draw_sprite(&mut self, x: u8, y: u8, sprite_addr: U12, sprite_height: U4) {
-        let sprite_data =
-            &self.ram[sprite_addr as usize..(sprite_addr + sprite_height as u16) as usize];
-        for curr_y in y..y + sprite_height {
-            if curr_y >= Self::DISPLAY_HEIGHT as u8 {
-                break;
+    // Draws on each plane of `planes` in order, with the next `sprite_height` bytes.
+    // VF is set if a pixel of any of them was turned off.
+    fn draw_sprite(
+        &mut self,
+        x: u8,
+        y: u8,
+        sprite_addr: U12,
+        sprite_height: U4,
+        planes: u8,
+    ) -> u32 {
+        let mut erased = 0;
+        let mut addr = sprite_addr as usize;
+        for plane in 0..2 {
+            if planes & (1 << plane) != 0 {
+                erased += self.draw_plane(plane, x, y, addr, sprite_height);
+                addr += sprite_height as usize;
             }
-            for curr_x in x..x + 8 {
-                if curr_x >= Self::DISPLAY_WIDTH as u8 {
+        }
+        self.registers.set(15, (erased > 0) as u8);
+        erased
+    }
+
+    fn draw_plane(&mut self, plane: usize, x: u8, y: u8, sprite_addr: usize, height: U4) -> u32 {
+        let wrap = self.options.wrap_sprites;
+        let display = if plane == 0 {
+            &mut self.display
+        } else {
+            &mut self.plane2
+        };
+        let mut erased = 0;
+        for row in 0..height as usize {
+            let mut pixel_y = y as usize + row;
+            if pixel_y >= Self::DISPLAY_HEIGHT {
+                if !wrap {
                     break;
                 }
-                let pixel_x = x as usize + 8 - (curr_x as usize - x as usize);
+                pixel_y %= Self::DISPLAY_HEIGHT;
+            }
+            // Rows past the end of the RAM are blank
+            let sprite_byte = self.ram.get(sprite_addr + row).copied().unwrap_or(0);
+            for bit in 0..8 {
+                let mut pixel_x = x as usize + bit;
                 if pixel_x >= Self::DISPLAY_WIDTH {
-                    break;
+                    if !wrap {
+                        break;
+                    }
+                    pixel_x %= Self::DISPLAY_WIDTH;
                 }
-                let pixel = &mut self.display[curr_y as usize][pixel_x];
-                let sprite_value = ((sprite_data[(curr_y - y) as usize] >> (curr_x - x)) & 1) == 1;
-                if *pixel && sprite_value {
-                    self.registers.set(15, 1);
-                } else {
-                    self.registers.set(15, 0);
+                if sprite_byte & (0x80 >> bit) != 0 {
+                    let pixel = &mut display[pixel_y][pixel_x];
+                    erased += *pixel as u32;
+                    *pixel = !*pixel;
                 }
-                *pixel ^= sprite_value;
             }
         }
+        erased
     }
 
     fn char_index(&self, c: u8) -> U12 {
         Self::FONT_START as U12 + 5 * (c & 0xF) as U12
     }
-    fn incr_pc(&mut self) {
-        self.registers.pc += 2;
+    // Address of the high-resolution glyph of the hex digit `c`
+    pub fn big_char_index(&self, c: u8) -> U12 {
+        Self::BIG_FONT_START as U12 + 10 * (c & 0xF) as U12
+    }
+    fn incr_pc(&mut self) -> Result<(), Chip8Error> {
+        self.registers.pc = self.checked_addr(self.registers.pc as usize + 2)?;
+        Ok(())
+    }
+    // Address of the executing instruction, PC having already moved past it (maybe
+    // wrapping around with `wrap_addresses`)
+    fn instruction_addr(&self) -> U12 {
+        ((self.registers.pc as usize + Self::RAM_SIZE - 2) % Self::RAM_SIZE) as U12
+    }
+    // Makes the executing instruction run again at the next cycle
+    fn rewind_pc(&mut self) {
+        self.registers.pc = self.instruction_addr();
+    }
+    // Whether `len` bytes from `start` are all in the RAM
+    fn check_range(&self, start: usize, len: usize) -> Result<(), Chip8Error> {
+        if start + len > Self::RAM_SIZE {
+            return Err(Chip8Error::OutOfBounds(Self::RAM_SIZE.max(start)));
+        }
+        Ok(())
+    }
+    // Whether a ROM instruction can write from `start` on, see `protect_reserved`
+    fn check_writable(&self, start: usize) -> Result<(), Chip8Error> {
+        if self.options.protect_reserved && start < Self::RAM_ROM_START {
+            return Err(Chip8Error::WriteProtected(start));
+        }
+        Ok(())
+    }
+    // New value of PC or I, masked to 12 bits or rejected past the address space
+    // depending on `wrap_addresses`
+    fn checked_addr(&self, addr: usize) -> Result<U12, Chip8Error> {
+        if addr < Self::RAM_SIZE {
+            Ok(addr as U12)
+        } else if self.options.wrap_addresses {
+            Ok((addr % Self::RAM_SIZE) as U12)
+        } else {
+            Err(Chip8Error::AddressOverflow(addr))
+        }
     }
     fn init_ram(font: Font) -> Ram {
         let mut ram = [0; Self::RAM_SIZE];
         ram[Self::FONT_START..(Self::FONT_START + Self::FONT_SIZE)]
             .copy_from_slice(&font[..(Self::FONT_START + Self::FONT_SIZE - Self::FONT_START)]);
+        ram[Self::BIG_FONT_START..(Self::BIG_FONT_START + Self::BIG_FONT_SIZE)]
+            .copy_from_slice(&Self::BIG_FONT);
         ram
     }
 
+    // Glyphs are drawn with the 4 leftmost bits of each byte
+    pub fn validate_font(font: &Font) -> Result<(), Chip8Error> {
+        for (digit, glyph) in font.chunks(5).enumerate() {
+            if glyph.iter().all(|&b| b == 0) || glyph.iter().any(|&b| b & 0x0F != 0) {
+                return Err(Chip8Error::InvalidFont(digit as u8));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn validate_big_font(font: &BigFont) -> Result<(), Chip8Error> {
+        match font
+            .chunks(10)
+            .position(|glyph| glyph.iter().all(|&b| b == 0))
+        {
+            Some(digit) => Err(Chip8Error::InvalidFont(digit as u8)),
+            None => Ok(()),
+        }
+    }
+
+    pub fn set_font(&mut self, font: &Font) -> Result<(), Chip8Error> {
+        Self::validate_font(font)?;
+        self.ram[Self::FONT_START..(Self::FONT_START + Self::FONT_SIZE)].copy_from_slice(font);
+        self.ram_written(Self::FONT_START, Self::FONT_START + Self::FONT_SIZE);
+        Ok(())
+    }
+
+    // Replaces the SUPER-CHIP high-resolution digits
+    pub fn set_big_font(&mut self, font: &BigFont) -> Result<(), Chip8Error> {
+        Self::validate_big_font(font)?;
+        self.ram[Self::BIG_FONT_START..(Self::BIG_FONT_START + Self::BIG_FONT_SIZE)]
+            .copy_from_slice(font);
+        self.ram_written(
+            Self::BIG_FONT_START,
+            Self::BIG_FONT_START + Self::BIG_FONT_SIZE,
+        );
+        Ok(())
+    }
+
+    // Forgets the decoded instructions overlapping `start..end`, including the one starting
+    // on the byte before. Every write to the RAM must go through it for self-modifying code.
+    pub(crate) fn ram_written(&mut self, start: usize, end: usize) {
+        if let Some(decoded) = &mut self.decoded {
+            let end = end.min(Self::RAM_SIZE);
+            decoded[start.saturating_sub(1).min(end)..end].fill(None);
+        }
+    }
+
+    fn decode(&mut self, opcode: u16) -> Chip8Instr {
+        match &mut self.decoded {
+            Some(decoded) => {
+                *decoded[self.registers.pc as usize].get_or_insert_with(|| Chip8Instr::from(opcode))
+            }
+            None => Chip8Instr::from(opcode),
+        }
+    }
+
     fn debugln(&self, msg: &str) {
-        if self.options.debug {
-            println!("{msg}");
+        log_debug(self.options.debug, msg);
+    }
+
+    fn warnln(&self, msg: &str) {
+        log_warn(self.options.debug, msg);
+    }
+}
+
+// Debug output: a `log::debug!` record with the `log` feature, else printed when the
+// `debug` option is set
+fn log_debug(debug: bool, msg: &str) {
+    #[cfg(feature = "log")]
+    {
+        let _ = debug;
+        log::debug!("{msg}");
+    }
+    #[cfg(all(feature = "std", not(feature = "log")))]
+    if debug {
+        println!("{msg}");
+    }
+    #[cfg(not(any(feature = "std", feature = "log")))]
+    let _ = (debug, msg);
+}
+
+// Same as `log_debug` at the warning level
+fn log_warn(debug: bool, msg: &str) {
+    #[cfg(feature = "log")]
+    {
+        let _ = debug;
+        log::warn!("{msg}");
+    }
+    #[cfg(not(feature = "log"))]
+    log_debug(debug, &format!("warning: {msg}"));
+}
+// FNV-1a over one byte (0 or 1) per pixel in row-major order,
+// independent of the platform's endianness and pointer width
+pub fn hash_display(display: &Display) -> u64 {
+    const FNV_OFFSET: u64 = 0xcbf29ce484222325;
+    const FNV_PRIME: u64 = 0x100000001b3;
+    display.iter().flatten().fold(FNV_OFFSET, |hash, &pixel| {
+        (hash ^ pixel as u64).wrapping_mul(FNV_PRIME)
+    })
+}
+
+// 8 pixels per byte, rows top to bottom and each row left to right: byte n holds the
+// pixels 8n to 8n+7 in row-major order, the leftmost in the most significant bit
+// (set when lit). 256 bytes for the 64x32 display, the layout of PBM (P4) rasters.
+pub fn pack_display(display: &Display) -> Vec<u8> {
+    display
+        .iter()
+        .flat_map(|row| row.chunks(8))
+        .map(|pixels| {
+            pixels
+                .iter()
+                .enumerate()
+                .fold(0, |byte, (bit, &on)| byte | (on as u8) << (7 - bit))
+        })
+        .collect()
+}
+
+// Extensions whose opcodes appear in a ROM loaded at 0x200. Only the instructions
+// reachable from the entry point are decoded, like in `disassembler::disassemble`,
+// so sprite data isn't mistaken for opcodes. A heuristic: the flow is followed
+// through both branches of skips, reaching an opcode doesn't mean it is run.
+pub fn detect_extensions(rom: &[u8]) -> BTreeSet<Extension> {
+    scan_extensions(rom, Chip8VM::RAM_ROM_START)
+}
+
+fn scan_extensions(rom: &[u8], base: usize) -> BTreeSet<Extension> {
+    let mut extensions = BTreeSet::new();
+    let mut visited = BTreeSet::new();
+    let mut pending = alloc::vec![base];
+    while let Some(addr) = pending.pop() {
+        let Some(offset) = addr.checked_sub(base) else {
+            continue;
+        };
+        let (Some(&high), Some(&low)) = (rom.get(offset), rom.get(offset + 1)) else {
+            continue;
+        };
+        if !visited.insert(addr) {
+            continue;
         }
+        let opcode = u16::from_be_bytes([high, low]);
+        let (n, nn) = (opcode & 0xF, opcode & 0xFF);
+        let extension = match Chip8Instr::from(opcode) {
+            Chip8Instr::Jump(nnn) => {
+                pending.push(nnn as usize);
+                None
+            }
+            Chip8Instr::Call(nnn) => {
+                pending.extend([nnn as usize, addr + 2]);
+                None
+            }
+            // The offset is only known at run time, the table start is the best guess
+            Chip8Instr::JumpOff(nnn) => {
+                pending.push(nnn as usize);
+                None
+            }
+            Chip8Instr::Return => None,
+            Chip8Instr::SkipEqImm(..)
+            | Chip8Instr::SkipNeImm(..)
+            | Chip8Instr::SkipEqReg(..)
+            | Chip8Instr::SkipNeReg(..)
+            | Chip8Instr::KeyUp(_)
+            | Chip8Instr::KeyDown(_) => {
+                pending.extend([addr + 2, addr + 4]);
+                None
+            }
+            // 00FD exit
+            Chip8Instr::Sys(0x0FD) => Some(Extension::SuperChip),
+            // 00CN scroll down, 00FB-00FC scroll right/left, 00FE-00FF low/high res
+            Chip8Instr::Sys(nnn)
+                if (nnn & 0xFF0 == 0x0C0 && n != 0) || (0x0FB..=0x0FF).contains(&nnn) =>
+            {
+                pending.push(addr + 2);
+                Some(Extension::SuperChip)
+            }
+            // 00DN scroll up
+            Chip8Instr::Sys(nnn) if nnn & 0xFF0 == 0x0D0 && n != 0 => {
+                pending.push(addr + 2);
+                Some(Extension::XoChip)
+            }
+            // DXY0 16x16 sprite, FX30 big digit
+            Chip8Instr::Display(_, _, 0) | Chip8Instr::BigChar(_) => {
+                pending.push(addr + 2);
+                Some(Extension::SuperChip)
+            }
+            Chip8Instr::Unknown => {
+                let extension = match opcode >> 12 {
+                    // FX75, FX85 flag registers
+                    0xF if nn == 0x75 || nn == 0x85 => Extension::SuperChip,
+                    // F000 NNNN long I, the address isn't an instruction
+                    0xF if opcode == 0xF000 => {
+                        pending.push(addr + 4);
+                        extensions.insert(Extension::XoChip);
+                        continue;
+                    }
+                    // FN01 planes, F002 audio, FX3A pitch
+                    0xF if nn == 0x01 || opcode == 0xF002 || nn == 0x3A => Extension::XoChip,
+                    // 5XY2, 5XY3 save and load a register range
+                    5 if n == 2 || n == 3 => Extension::XoChip,
+                    // Not code after all
+                    _ => continue,
+                };
+                pending.push(addr + 2);
+                Some(extension)
+            }
+            _ => {
+                pending.push(addr + 2);
+                None
+            }
+        };
+        extensions.extend(extension);
     }
+    extensions
+}
 
-    fn debug(&self, msg: &str) {
-        if self.options.debug {
-            print!("{msg}");
+// Runs `rom` for exactly `cycles` instructions without rendering and returns the final display.
+// The rng is seeded and timers tick every FREQ/60 instructions instead of following
+// the wall clock, so the result only depends on the arguments.
+pub fn run_headless(rom: &[u8], cycles: usize, seed: u64) -> Result<Display, Chip8Error> {
+    let mut vm = Chip8VM::new(
+        None,
+        None,
+        Some(Chip8VMOptions {
+            hide_display: true,
+            manual_timers: true,
+            ..Default::default()
+        }),
+    );
+    vm.set_rng(Box::new(StdRng::seed_from_u64(seed)));
+    vm.load_rom(rom);
+    let cycles_per_tick = (Chip8VM::FREQ / Timers::TIMER_FREQ) as usize;
+    for cycle in 1..=cycles {
+        vm.run_once()?;
+        if cycle % cycles_per_tick == 0 {
+            vm.tick_timers();
         }
     }
+    Ok(vm.display)
 }
+
 #[cfg(test)]
-mod tests {
+pub(crate) mod tests {
     use super::*;
+    use alloc::{string::ToString, vec};
+
+    // No timer thread, so tests don't depend on the wall clock, and no terminal output.
+    // Shared by the tests of the other modules, which set the options they need on it.
+    pub(crate) fn test_vm() -> Chip8VM {
+        Chip8VM::new(
+            None,
+            None,
+            Some(Chip8VMOptions {
+                hide_display: true,
+                manual_timers: true,
+                ..Default::default()
+            }),
+        )
+    }
+
     #[test]
     fn default_new() {
-        let vm = Chip8VM::new(None, None, None);
+        let vm = test_vm();
         assert_eq!(
             vm.ram[Chip8VM::FONT_START..Chip8VM::FONT_START + Chip8VM::FONT_SIZE],
             Chip8VM::FONT
@@ -681,31 +3037,1932 @@ mod tests {
 
     #[test]
     fn load_empty() {
-        let mut vm = Chip8VM::new(None, None, None);
-        let before = vm.ram.clone();
+        let mut vm = test_vm();
+        let before = vm.ram;
         vm.load_rom(&[]);
         assert_eq!(before, vm.ram);
     }
     #[test]
     #[should_panic]
     fn load_too_big() {
-        let mut vm = Chip8VM::new(None, None, None);
+        let mut vm = test_vm();
         vm.load_rom(&[1; 4096 - 511]);
     }
 
     #[test]
     fn load_fit() {
-        let mut vm = Chip8VM::new(None, None, None);
+        let mut vm = test_vm();
         vm.load_rom(&[1; 4096 - 512]);
     }
 
+    #[test]
+    fn mem_access() {
+        let mut vm = test_vm();
+        assert_eq!(vm.read_mem(0x50), Some(0xF0));
+        assert_eq!(vm.read_mem(0x1000), None);
+        vm.write_mem(0x300, 0xAB).unwrap();
+        assert_eq!(vm.read_mem(0x300), Some(0xAB));
+        assert_eq!(
+            vm.write_mem(0x1000, 1),
+            Err(Chip8Error::OutOfBounds(0x1000))
+        );
+        assert_eq!(vm.read_mem_slice(0x300, 2), Some(&[0xAB, 0][..]));
+        assert_eq!(vm.read_mem_slice(0xFFF, 2), None);
+    }
+
+    #[test]
+    fn decimal_through_mem() {
+        let mut vm = test_vm();
+        vm.registers.set(0, 254);
+        vm.registers.i = 0x300;
+        vm.execute(Chip8Instr::Decimal(0)).unwrap();
+        assert_eq!(vm.read_mem_slice(0x300, 3), Some(&[2, 5, 4][..]));
+    }
+
+    #[test]
+    fn skip_instructions() {
+        let tests = [
+            (Chip8Instr::SkipEqImm(0, 0x12), 0x12, 0, true),
+            (Chip8Instr::SkipEqImm(0, 0x13), 0x12, 0, false),
+            (Chip8Instr::SkipNeImm(0, 0x13), 0x12, 0, true),
+            (Chip8Instr::SkipNeImm(0, 0x12), 0x12, 0, false),
+            (Chip8Instr::SkipEqReg(0, 1), 0x12, 0x12, true),
+            (Chip8Instr::SkipEqReg(0, 1), 0x12, 0x13, false),
+            (Chip8Instr::SkipNeReg(0, 1), 0x12, 0x13, true),
+            (Chip8Instr::SkipNeReg(0, 1), 0x12, 0x12, false),
+        ];
+        for (instr, v0, v1, skips) in tests {
+            let mut vm = test_vm();
+            vm.registers.set(0, v0);
+            vm.registers.set(1, v1);
+            let pc = vm.registers.pc;
+            vm.execute(instr).unwrap();
+            assert_eq!(vm.registers.pc, if skips { pc + 2 } else { pc });
+        }
+    }
+
+    #[test]
+    fn manual_timers() {
+        let mut vm = test_vm();
+        vm.registers.set(0, 10);
+        vm.execute(Chip8Instr::SetDelay(0)).unwrap();
+        vm.execute(Chip8Instr::SetBuzzer(0)).unwrap();
+        for _ in 0..3 {
+            vm.tick_timers();
+        }
+        vm.execute(Chip8Instr::GetDelay(1)).unwrap();
+        assert_eq!(vm.registers.get(1), 7);
+        assert_eq!(vm.timers.get().buzzer, 7);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn timer_thread_stops_on_drop() {
+        let vm = Chip8VM::new(None, None, None);
+        let stop = match &vm.timers {
+            TimersHandle::Threaded(w) => Arc::clone(&w.stop),
+            TimersHandle::Manual(_) => panic!("threaded timers by default"),
+        };
+        drop(vm);
+        assert!(stop.load(Ordering::Relaxed));
+        assert_eq!(Arc::strong_count(&stop), 1);
+    }
+
+    #[test]
+    fn keypad() {
+        let mut keypad = Keypad::default();
+        keypad.press(0xA);
+        keypad.press(3);
+        keypad.press(16);
+        assert!(keypad.is_pressed(0xA) && keypad.is_pressed(3));
+        assert!(!keypad.is_pressed(0) && !keypad.is_pressed(16));
+        keypad.release(0xA);
+        assert!(!keypad.is_pressed(0xA));
+        assert_eq!(keypad, Keypad(1 << 3));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn pacer_rate() {
+        let mut pacer = Pacer::new();
+        let start = Instant::now();
+        let mut cycles = 0;
+        while start.elapsed() < Duration::from_secs(1) {
+            pacer.wait(700);
+            cycles += 1;
+        }
+        assert!((690..=710).contains(&cycles), "{cycles} cycles in 1s");
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn draw_does_not_block() {
+        let mut vm = test_vm();
+        let start = Instant::now();
+        for _ in 0..10 {
+            vm.execute(Chip8Instr::Display(0, 0, 5)).unwrap();
+        }
+        assert!(vm.display_changed);
+        assert!(start.elapsed() < Duration::from_millis(100));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn pacer_due() {
+        let mut pacer = Pacer::new();
+        assert!(pacer.due(60));
+        assert!(!pacer.due(60));
+        thread::sleep(Duration::from_millis(20));
+        assert!(pacer.due(60));
+        assert!(!pacer.due(60));
+    }
+
+    #[test]
+    fn shift_opcodes_vf() {
+        // opcode, old_shift, VX, VF
+        let cases = [
+            // V1 = V0 >> 1 with V0 = 0x81, bit 0 is 1
+            (0x8106, true, 0x40, 1),
+            // V1 = V1 >> 1 with V1 = 0x02, bit 0 is 0
+            (0x8106, false, 0x01, 0),
+            // V1 = V0 << 1, bit 7 is 1
+            (0x810E, true, 0x02, 1),
+            // V1 = V1 << 1, bit 7 is 0
+            (0x810E, false, 0x04, 0),
+            // Same register, both quirks agree
+            (0x8006, true, 0x40, 1),
+            (0x8006, false, 0x40, 1),
+            (0x800E, true, 0x02, 1),
+            (0x800E, false, 0x02, 1),
+        ];
+        for (opcode, old_shift, vx, vf) in cases {
+            let mut vm = test_vm();
+            vm.options.old_shift = old_shift;
+            vm.load_rom(&u16::to_be_bytes(opcode));
+            vm.registers.set(0, 0x81);
+            vm.registers.set(1, 0x02);
+            vm.registers.set(0xF, 0xAA);
+            vm.run_once().unwrap();
+            let x = (opcode >> 8 & 0xF) as U4;
+            let case = format!("{opcode:04X}, old_shift {old_shift}");
+            assert_eq!(vm.registers.get(x), vx, "VX of {case}");
+            assert_eq!(vm.registers.get(0xF), vf, "VF of {case}");
+        }
+    }
+
+    #[test]
+    fn shift_into_vf() {
+        for old_shift in [false, true] {
+            for (op, shifted) in [(6, 0x40), (0xE, 0x02)] {
+                let mut vm = test_vm();
+                vm.options.old_shift = old_shift;
+                vm.registers.set(0xF, 0x81);
+                vm.registers.set(0, 0x81);
+                vm.execute(Chip8Instr::ShiftOp(0xF, 0, op)).unwrap();
+                assert_eq!(vm.registers.get(0xF), 1, "op {op:x}, old_shift {old_shift}");
+
+                vm.registers.set(1, 0x81);
+                vm.execute(Chip8Instr::ShiftOp(1, 0, op)).unwrap();
+                assert_eq!(vm.registers.get(1), shifted);
+            }
+        }
+    }
+
+    #[test]
+    fn arithm_into_vf() {
+        let tests = [
+            (4, 0xFF, 0x02, 1),
+            (4, 0x01, 0x02, 0),
+            (5, 0x03, 0x02, 1),
+            (5, 0x01, 0x02, 0),
+            (7, 0x01, 0x02, 1),
+            (7, 0x03, 0x02, 0),
+        ];
+        for (op, vf, v1, flag) in tests {
+            let mut vm = test_vm();
+            vm.registers.set(0xF, vf);
+            vm.registers.set(1, v1);
+            vm.execute(Chip8Instr::ArithmOp(0xF, 1, op)).unwrap();
+            assert_eq!(vm.registers.get(0xF), flag, "8F1{op:x} with VF={vf:x}");
+        }
+    }
+
+    #[test]
+    fn timers_start_at_zero() {
+        let mut vm = Chip8VM::new(None, None, None);
+        vm.registers.set(0, 0xFF);
+        vm.execute(Chip8Instr::GetDelay(0)).unwrap();
+        assert_eq!(vm.registers.get(0), 0);
+        assert_eq!(vm.timers.get().buzzer, 0);
+
+        let mut vm = Chip8VM::new(
+            None,
+            None,
+            Some(Chip8VMOptions {
+                initial_delay: 120,
+                manual_timers: true,
+                ..Default::default()
+            }),
+        );
+        vm.execute(Chip8Instr::GetDelay(0)).unwrap();
+        assert_eq!(vm.registers.get(0), 120);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn frame_callback() {
+        let mut vm = test_vm();
+        let frames = Arc::new(Mutex::new(0));
+        let counter = Arc::clone(&frames);
+        vm.display[0][0] = true;
+        vm.set_on_frame(Box::new(move |display, keypad| {
+            assert!(display[0][0]);
+            keypad.press(3);
+            *counter.lock().unwrap() += 1;
+        }));
+        vm.frame();
+        vm.frame();
+        assert_eq!(*frames.lock().unwrap(), 2);
+        assert!(vm.keypad.is_pressed(3));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn key_poll() {
+        let mut vm = Chip8VM::new(
+            None,
+            None,
+            Some(Chip8VMOptions {
+                hide_display: true,
+                manual_timers: true,
+                terminal_keypad: true,
+                ..Default::default()
+            }),
+        );
+        let host_keys = Arc::new(Mutex::new(1 << 0xA));
+        let keys = Arc::clone(&host_keys);
+        vm.set_key_poll(Box::new(move || *keys.lock().unwrap()));
+        vm.set_on_frame(Box::new(|_, keypad| {
+            assert!(keypad.is_pressed(0xA) || keypad.is_pressed(0x2));
+            keypad.press(0x1);
+        }));
+        vm.pre_run();
+        assert!(vm.terminal_input.is_none());
+        // SKP V0
+        vm.load_rom(&[0xE0, 0x9E]);
+        vm.registers.set(0, 0xA);
+        vm.set_key(0x5, true).unwrap();
+        vm.frame();
+        assert_eq!(vm.keypad_state(), 1 << 0xA | 1 << 0x1);
+        vm.run_once().unwrap();
+        assert_eq!(vm.registers.pc, 0x204);
+
+        *host_keys.lock().unwrap() = 1 << 0x2;
+        vm.frame();
+        assert_eq!(vm.keypad_state(), 1 << 0x2 | 1 << 0x1);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn frame_signal() {
+        let mut vm = test_vm();
+        let signal = vm.frame_signal();
+        let stop = Arc::new(AtomicBool::new(false));
+        let worker = {
+            let stop = Arc::clone(&stop);
+            thread::spawn(move || {
+                while !stop.load(Ordering::Relaxed) {
+                    thread::sleep(Duration::from_millis(5));
+                    vm.frame();
+                }
+            })
+        };
+        let first = signal.wait_for_frame().unwrap();
+        let second = signal.clone().wait_for_frame().unwrap();
+        stop.store(true, Ordering::Relaxed);
+        worker.join().unwrap();
+        assert!(first >= 1);
+        assert!(second > first);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn frame_signal_after_run() {
+        let mut vm = test_vm();
+        let signal = vm.frame_signal();
+        // Waits on the halted VM until `run` returns
+        let waiter = thread::spawn(move || while signal.wait_for_frame().is_some() {});
+        // LD V0, 1 ; JP 0x202
+        vm.load_rom(&[0x60, 0x01, 0x12, 0x02]);
+        vm.options.halt_on_self_jump = true;
+        vm.run().unwrap();
+        waiter.join().unwrap();
+        assert!(vm.frame_signal().wait_for_frame().is_none());
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn opcode_stats() {
+        let mut vm = test_vm();
+        vm.options.opcode_stats = true;
+        // LD V0, 1; ADD V0, 1; ADD V0, 1
+        vm.load_rom(&[0x60, 0x01, 0x70, 0x01, 0x70, 0x01]);
+        for _ in 0..3 {
+            vm.run_once().unwrap();
+        }
+        assert_eq!(vm.cycles(), 3);
+        let stats = vm.opcode_stats();
+        assert_eq!(stats.len(), 2);
+        assert_eq!(stats["Set"], 1);
+        assert_eq!(stats["Add"], 2);
+
+        let mut vm = test_vm();
+        vm.load_rom(&[0x60, 0x01]);
+        vm.run_once().unwrap();
+        assert_eq!(vm.cycles(), 1);
+        assert!(vm.opcode_stats().is_empty());
+    }
+
+    #[test]
+    fn seeded_rand() {
+        use rand::{rngs::StdRng, SeedableRng};
+
+        let expected: u8 = StdRng::seed_from_u64(42).gen();
+        let mut vm = test_vm();
+        vm.set_rng(Box::new(StdRng::seed_from_u64(42)));
+        vm.load_rom(&[0xC0, 0xFF]);
+        vm.run_once().unwrap();
+        assert_eq!(vm.registers.get(0), expected);
+
+        let mut vm = test_vm();
+        vm.set_rng(Box::new(StdRng::seed_from_u64(42)));
+        vm.execute(Chip8Instr::Rand(1, 0x0F)).unwrap();
+        assert_eq!(vm.registers.get(1), expected & 0x0F);
+    }
+
+    #[test]
+    fn headless_ibm() {
+        let rom = include_bytes!("../ibm.ch8");
+        let display = run_headless(rom, 100, 0).unwrap();
+        assert!(display.iter().flatten().any(|&p| p));
+        assert_eq!(display, run_headless(rom, 100, 0).unwrap());
+        assert_eq!(run_headless(rom, 0, 0).unwrap(), Chip8VM::DISPLAY_EMPTY);
+    }
+
+    #[test]
+    fn display_hash() {
+        let mut vm = test_vm();
+        assert_eq!(vm.display_hash(), 0x28c31cf8df2ec325);
+        vm.display[0][0] = true;
+        assert_eq!(vm.display_hash(), 0xda2a54478fa6a324);
+        vm.display[0][0] = false;
+        vm.display[31][63] = true;
+        assert_ne!(vm.display_hash(), 0x28c31cf8df2ec325);
+    }
+
+    #[test]
+    fn key_skips() {
+        let mut vm = test_vm();
+        vm.registers.set(0, 0xB);
+        let pc = vm.registers.pc;
+        vm.execute(Chip8Instr::KeyDown(0)).unwrap();
+        assert_eq!(vm.registers.pc, pc);
+        vm.execute(Chip8Instr::KeyUp(0)).unwrap();
+        assert_eq!(vm.registers.pc, pc + 2);
+
+        vm.keypad_mut().press(0xB);
+        vm.execute(Chip8Instr::KeyDown(0)).unwrap();
+        assert_eq!(vm.registers.pc, pc + 4);
+        vm.execute(Chip8Instr::KeyUp(0)).unwrap();
+        assert_eq!(vm.registers.pc, pc + 4);
+    }
+
+    #[test]
+    fn get_key_keeps_timers_running() {
+        let mut vm = test_vm();
+        // LD V1, K
+        vm.load_rom(&[0xF1, 0x0A]);
+        vm.timers.modify(|t| t.delay = 10);
+        for _ in 0..4 {
+            vm.run_once().unwrap();
+            vm.tick_timers();
+        }
+        assert_eq!(vm.timers.get().delay, 6);
+        assert_eq!(vm.registers.pc, 0x200);
+
+        // Completes on release
+        vm.keypad_mut().press(0x7);
+        vm.run_once().unwrap();
+        vm.run_once().unwrap();
+        assert_eq!(vm.registers.pc, 0x200);
+        vm.keypad_mut().release(0x7);
+        vm.run_once().unwrap();
+        assert_eq!(vm.registers.pc, 0x202);
+        assert_eq!(vm.registers.get(1), 0x7);
+    }
+
+    #[test]
+    fn key_api() {
+        let mut vm = test_vm();
+        vm.set_key(0x4, true).unwrap();
+        assert!(vm.key_state(0x4));
+        assert_eq!(vm.keypad_state(), 1 << 4);
+        assert_eq!(vm.set_key(0x10, true), Err(Chip8Error::InvalidKey(0x10)));
+        assert!(!vm.key_state(0x10));
+
+        // SKP V0 then SKNP V0
+        vm.load_rom(&[0xE0, 0x9E, 0x00, 0x00, 0xE0, 0xA1]);
+        vm.registers.set(0, 0x4);
+        vm.run_once().unwrap();
+        assert_eq!(vm.registers.pc, 0x204);
+        vm.set_keypad_state(0);
+        vm.run_once().unwrap();
+        assert_eq!(vm.registers.pc, 0x208);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn renderer_on_change() {
+        struct Counter(Arc<Mutex<u32>>);
+        impl Renderer for Counter {
+            fn render(&mut self, _display: &Display) {
+                *self.0.lock().unwrap() += 1;
+            }
+        }
+        let renders = Arc::new(Mutex::new(0));
+        let mut vm = test_vm();
+        vm.set_renderer(Box::new(Counter(Arc::clone(&renders))));
+        vm.frame();
+        assert_eq!(*renders.lock().unwrap(), 0);
+        vm.execute(Chip8Instr::Clear).unwrap();
+        vm.frame();
+        vm.frame();
+        assert_eq!(*renders.lock().unwrap(), 1);
+    }
+
+    #[test]
+    fn load_rom_at_custom_start() {
+        let mut vm = Chip8VM::new(
+            None,
+            None,
+            Some(Chip8VMOptions {
+                manual_timers: true,
+                rom_start: Some(0x600),
+                hide_display: true,
+                ..Default::default()
+            }),
+        );
+        assert_eq!(vm.registers.pc, 0x600);
+        vm.load_rom(&[0x12, 0x34, 0x56]);
+        assert_eq!(vm.read_mem_slice(0x600, 3), Some(&[0x12, 0x34, 0x56][..]));
+        assert_eq!(vm.read_mem(0x200), Some(0));
+        assert_eq!(vm.fetch_instruction(), 0x1234);
+    }
+
+    #[test]
+    #[should_panic(expected = "ROM too big: 257B for 256B available")]
+    fn rom_too_big_for_custom_start() {
+        let mut vm = Chip8VM::new(
+            None,
+            None,
+            Some(Chip8VMOptions {
+                manual_timers: true,
+                rom_start: Some(0xF00),
+                hide_display: true,
+                ..Default::default()
+            }),
+        );
+        vm.load_rom(&[0; 0x101]);
+    }
+
+    #[test]
+    fn fonts() {
+        assert_eq!(Chip8VM::validate_font(&Chip8VM::FONT), Ok(()));
+        assert_eq!(Chip8VM::validate_big_font(&Chip8VM::BIG_FONT), Ok(()));
+
+        let mut vm = test_vm();
+        assert_eq!(vm.big_char_index(0xA), 0xA0 + 100);
+        assert_eq!(
+            vm.read_mem_slice(vm.big_char_index(1), 10),
+            Some(&Chip8VM::BIG_FONT[10..20])
+        );
+
+        let mut font = Chip8VM::FONT;
+        font[7] = 0x21;
+        assert_eq!(vm.set_font(&font), Err(Chip8Error::InvalidFont(1)));
+        font[7] = 0x20;
+        font[75..].fill(0);
+        assert_eq!(vm.set_font(&font), Err(Chip8Error::InvalidFont(0xF)));
+
+        let mut big_font = Chip8VM::BIG_FONT;
+        assert_eq!(vm.set_big_font(&big_font), Ok(()));
+        big_font[20..30].fill(0);
+        assert_eq!(vm.set_big_font(&big_font), Err(Chip8Error::InvalidFont(2)));
+    }
+
+    #[test]
+    fn big_char() {
+        let mut vm = test_vm();
+        // LD V2, 7 ; LD HF, V2
+        vm.load_rom(&[0x62, 0x07, 0xF2, 0x30]);
+        vm.run_once().unwrap();
+        vm.run_once().unwrap();
+        assert_eq!(vm.registers.i, 0xA0 + 70);
+        assert_eq!(
+            vm.read_mem_slice(vm.registers.i, 10),
+            Some(&Chip8VM::BIG_FONT[70..80])
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid font glyph")]
+    fn new_with_invalid_font() {
+        Chip8VM::new(None, Some([0xFF; 80]), None);
+    }
+
+    #[test]
+    fn profiles() {
+        let vip = Profile::CosmacVip.options();
+        assert!(vip.old_shift && vip.vf_reset && vip.display_wait && !vip.wrap_sprites);
+        let schip = Profile::SuperChip.options();
+        assert!(schip.new_jump_off && !schip.old_shift && !schip.incr_i_when_mem);
+        assert!(Profile::XoChip.options().wrap_sprites);
+
+        let options = Chip8VMOptions {
+            old_shift: false,
+            hide_display: true,
+            ..Profile::CosmacVip.options()
+        };
+        assert!(!options.old_shift && options.vf_reset);
+    }
+
+    #[test]
+    fn profile_memory_quirk() {
+        for (profile, i) in [
+            (Profile::CosmacVip, 0x304),
+            (Profile::Chip48, 0x303),
+            (Profile::SuperChip, 0x300),
+            (Profile::XoChip, 0x304),
+        ] {
+            let mut vm = test_vm();
+            vm.options = Chip8VMOptions {
+                hide_display: true,
+                manual_timers: true,
+                ..profile.options()
+            };
+            vm.registers.i = 0x300;
+            vm.registers.set(3, 7);
+            vm.execute(Chip8Instr::Save(3)).unwrap();
+            assert_eq!(vm.registers.i, i, "{profile:?}");
+            assert_eq!(vm.read_mem(0x303), Some(7));
+
+            vm.registers.i = 0x300;
+            vm.registers.set(3, 0);
+            vm.execute(Chip8Instr::Load(3)).unwrap();
+            assert_eq!(vm.registers.i, i, "{profile:?}");
+            assert_eq!(vm.registers.get(3), 7);
+        }
+    }
+
+    #[test]
+    fn vf_reset() {
+        for vf_reset in [false, true] {
+            let mut vm = test_vm();
+            vm.options.vf_reset = vf_reset;
+            vm.registers.set(15, 7);
+            vm.execute(Chip8Instr::BitOp(0, 1, 1)).unwrap();
+            assert_eq!(vm.registers.get(15), if vf_reset { 0 } else { 7 });
+        }
+    }
+
+    #[test]
+    fn display_wait() {
+        let mut vm = test_vm();
+        vm.options.display_wait = true;
+        // DRW V0, V0, 1 twice
+        vm.load_rom(&[0xD0, 0x01, 0xD0, 0x01]);
+        vm.run_once().unwrap();
+        assert_eq!(vm.registers.pc, 0x200);
+        vm.tick_timers();
+        vm.run_once().unwrap();
+        vm.run_once().unwrap();
+        assert_eq!(vm.registers.pc, 0x202);
+        vm.tick_timers();
+        vm.run_once().unwrap();
+        assert_eq!(vm.registers.pc, 0x204);
+    }
+
+    #[test]
+    fn sprite_clip_and_wrap() {
+        for wrap_sprites in [false, true] {
+            let mut vm = test_vm();
+            vm.options.wrap_sprites = wrap_sprites;
+            vm.ram[0x300..0x302].copy_from_slice(&[0b1100_0001, 0xFF]);
+            vm.draw_sprite(60, 31, 0x300, 2, 1);
+            assert!(vm.display[31][60] && vm.display[31][61] && !vm.display[31][62]);
+            assert_eq!(vm.display[31][3], wrap_sprites);
+            assert_eq!(vm.display[0][0], wrap_sprites);
+            assert_eq!(vm.registers.get(15), 0);
+
+            vm.draw_sprite(60, 31, 0x300, 1, 1);
+            assert!(!vm.display[31][60]);
+            assert_eq!(vm.registers.get(15), 1);
+        }
+    }
+
+    #[test]
+    fn draw_on_planes() {
+        let mut vm = test_vm();
+        // Plane 0 sprite, then plane 1 sprite
+        vm.ram[0x300..0x304].copy_from_slice(&[0xF0, 0x90, 0x0F, 0x09]);
+        vm.registers.i = 0x300;
+        let draw = Chip8Instr::Display(0, 0, 2);
+        vm.execute(draw).unwrap();
+        assert_eq!(
+            vm.display[0][..8],
+            [true, true, true, true, false, false, false, false]
+        );
+        assert_eq!(vm.plane2, Chip8VM::DISPLAY_EMPTY);
+
+        vm.select_planes(0b11);
+        vm.execute(draw).unwrap();
+        // Plane 0 drawn over itself, which collides
+        assert_eq!(vm.display, Chip8VM::DISPLAY_EMPTY);
+        assert_eq!(vm.registers.get(0xF), 1);
+        assert_eq!(
+            vm.plane(1).unwrap()[0][..8],
+            [false, false, false, false, true, true, true, true]
+        );
+        assert_eq!(vm.plane(1).unwrap()[1][4..8], [true, false, false, true]);
+
+        // Only on plane 1, the collision there sets VF
+        vm.select_planes(0b10);
+        vm.registers.i = 0x302;
+        vm.execute(draw).unwrap();
+        assert_eq!(vm.registers.get(0xF), 1);
+        assert_eq!(vm.plane2, Chip8VM::DISPLAY_EMPTY);
+
+        vm.select_planes(0b11);
+        vm.registers.i = 0xFFD;
+        assert_eq!(vm.execute(draw), Err(Chip8Error::OutOfBounds(0x1000)));
+
+        vm.display[1][1] = true;
+        vm.plane2[2][2] = true;
+        vm.select_planes(0b10);
+        vm.execute(Chip8Instr::Clear).unwrap();
+        assert!(vm.display[1][1] && !vm.plane2[2][2]);
+        assert_eq!(vm.plane(2), None);
+    }
+
+    #[test]
+    fn wrapped_pixels_collide() {
+        for wrap_sprites in [false, true] {
+            for (row, col) in [(31, 3), (0, 0), (0, 61)] {
+                let mut vm = test_vm();
+                vm.options.wrap_sprites = wrap_sprites;
+                vm.ram[0x300..0x302].copy_from_slice(&[0b1100_0001, 0xFF]);
+                vm.registers.i = 0x300;
+                vm.registers.set(0, 60);
+                vm.registers.set(1, 31);
+                // Only a pixel on the other side of an edge is lit
+                vm.display[row][col] = true;
+                vm.execute(Chip8Instr::Display(0, 1, 2)).unwrap();
+                assert_eq!(vm.registers.get(15), wrap_sprites as u8, "({row}, {col})");
+                assert_eq!(vm.display[row][col], !wrap_sprites);
+            }
+        }
+    }
+
+    #[test]
+    fn sprite_start_past_edge() {
+        for clip_sprite_start in [false, true] {
+            for wrap_sprites in [false, true] {
+                let mut vm = test_vm();
+                vm.options.clip_sprite_start = clip_sprite_start;
+                vm.options.wrap_sprites = wrap_sprites;
+                vm.ram[0x300] = 0b1000_0001;
+                vm.registers.i = 0x300;
+                vm.registers.set(0, 70);
+                vm.registers.set(1, 3);
+                vm.display[3][6] = true;
+                vm.execute(Chip8Instr::Display(0, 1, 1)).unwrap();
+                let case = format!("clip {clip_sprite_start}, wrap {wrap_sprites}");
+                // 70 % 64 = 6: the sprite covers columns 6 and 13, erasing the lit pixel
+                assert_eq!(vm.display[3][6], clip_sprite_start, "{case}");
+                assert_eq!(vm.display[3][13], !clip_sprite_start, "{case}");
+                assert_eq!(vm.registers.get(15), !clip_sprite_start as u8, "{case}");
+                assert_eq!(vm.display.iter().flatten().filter(|&&on| on).count(), 1);
+
+                // Same with VY past the bottom, VX on screen
+                let mut vm = test_vm();
+                vm.options.clip_sprite_start = clip_sprite_start;
+                vm.options.wrap_sprites = wrap_sprites;
+                vm.ram[0x300] = 0x80;
+                vm.registers.i = 0x300;
+                vm.registers.set(1, 40);
+                vm.execute(Chip8Instr::Display(0, 1, 1)).unwrap();
+                assert_eq!(vm.display[8][0], !clip_sprite_start, "{case}");
+            }
+        }
+        // The edge clipping still applies to a wrapped start: 126 % 64 = 62
+        let mut vm = test_vm();
+        vm.ram[0x300] = 0xFF;
+        vm.registers.i = 0x300;
+        vm.registers.set(0, 126);
+        vm.execute(Chip8Instr::Display(0, 1, 1)).unwrap();
+        assert!(vm.display[0][62] && vm.display[0][63] && !vm.display[0][0]);
+    }
+
+    #[test]
+    fn draw_collision_count() {
+        let mut vm = test_vm();
+        vm.ram[0x300..0x302].copy_from_slice(&[0b1111_0000, 0b1001_0000]);
+        vm.ram[0x302] = 0b1100_0000;
+        assert_eq!(vm.draw(10, 5, 0x300, 2), 0);
+        assert_eq!(vm.registers.get(15), 0);
+        assert!(vm.display_changed);
+        // Overlaps two pixels of the first row and one of the second
+        assert_eq!(vm.draw(10, 5, 0x301, 2), 3);
+        assert_eq!(vm.registers.get(15), 1);
+        assert_eq!(vm.display[5][10..14], [false, true, true, false]);
+        assert_eq!(vm.display[6][10..14], [false, true, false, true]);
+        // Coordinates wrap like DXYN
+        assert_eq!(vm.draw(10 + 64, 5 + 32, 0x300, 1), 2);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn pause_freezes_timers() {
+        let mut vm = Chip8VM::new(
+            None,
+            None,
+            Some(Chip8VMOptions {
+                hide_display: true,
+                ..Default::default()
+            }),
+        );
+        vm.timers.modify(|t| t.delay = 200);
+        vm.pre_run();
+        vm.pause();
+        assert!(vm.is_paused());
+        let delay = vm.timers.get().delay;
+        thread::sleep(Duration::from_millis(100));
+        assert_eq!(vm.timers.get().delay, delay);
+
+        vm.resume();
+        thread::sleep(Duration::from_millis(100));
+        let elapsed = delay - vm.timers.get().delay;
+        // About 6 ticks, without a burst catching up on the pause
+        assert!((3..=8).contains(&elapsed), "{elapsed} ticks");
+    }
+
+    #[test]
+    fn pause_manual_timers() {
+        let mut vm = test_vm();
+        vm.timers.modify(|t| t.delay = 10);
+        vm.pause();
+        vm.tick_timers();
+        assert_eq!(vm.timers.get().delay, 10);
+        vm.resume();
+        vm.tick_timers();
+        assert_eq!(vm.timers.get().delay, 9);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn double_buffer() {
+        let mut vm = test_vm();
+        // LD F, V0 ; DRW V0, V0, 5
+        vm.load_rom(&[0xF0, 0x29, 0xD0, 0x05]);
+        vm.run_once().unwrap();
+        vm.run_once().unwrap();
+        assert!(vm.display[0][0]);
+        assert_eq!(vm.front_display(), &Chip8VM::DISPLAY_EMPTY);
+        vm.frame();
+        assert_eq!(vm.front_display(), &vm.display);
+
+        // Forced present of a partial frame
+        vm.execute(Chip8Instr::Clear).unwrap();
+        vm.present();
+        assert_eq!(vm.front_display(), &Chip8VM::DISPLAY_EMPTY);
+    }
+
+    #[test]
+    fn phosphor_fade() {
+        let mut vm = test_vm();
+        vm.options.phosphor_frames = 3;
+        vm.display[1][2] = true;
+        vm.present();
+        assert_eq!(vm.intensity()[1][2], 255);
+        vm.display[1][2] = false;
+        let mut levels = Vec::new();
+        for _ in 0..4 {
+            vm.present();
+            levels.push(vm.intensity()[1][2]);
+        }
+        assert_eq!(levels, [170, 85, 0, 0]);
+        assert!(!vm.fading);
+
+        vm.options.phosphor_frames = 0;
+        vm.display[1][2] = true;
+        vm.present();
+        vm.display[1][2] = false;
+        vm.present();
+        assert_eq!(vm.intensity()[1][2], 0);
+    }
+
+    #[test]
+    fn ram_dump() {
+        let mut vm = test_vm();
+        vm.load_rom(b"CHIP-8\x00\xff and more text");
+        assert_eq!(
+            vm.dump_ram(0x200, 20),
+            "0200  43 48 49 50 2d 38 00 ff 20 61 6e 64 20 6d 6f 72  |CHIP-8.. and mor|\n\
+             0210  65 20 74 65                                      |e te|\n"
+        );
+        assert_eq!(vm.dump_ram(0xFFF, 16).lines().count(), 1);
+        assert_eq!(vm.dump_ram(0xFFFF, 16), "");
+    }
+
+    #[test]
+    fn instructions_iter() {
+        let mut vm = test_vm();
+        vm.load_rom(&[0x00, 0xE0, 0xA2, 0x2A, 0x23, 0x00, 0x12, 0x00]);
+        let calls: Vec<_> = vm
+            .instructions(0x200, 0x208)
+            .filter(|(_, instr)| matches!(instr, Chip8Instr::Call(_)))
+            .collect();
+        assert_eq!(calls, [(0x204, Chip8Instr::Call(0x300))]);
+        assert_eq!(
+            vm.instructions(0x200, 0x203).collect::<Vec<_>>(),
+            [(0x200, Chip8Instr::Clear), (0x202, Chip8Instr::SetI(0x22A))]
+        );
+        assert_eq!(vm.instructions(0xFFE, 0xFFFF).count(), 1);
+    }
+
+    #[test]
+    fn call_stack() {
+        let mut vm = test_vm();
+        vm.options.debug_stack = true;
+        // CALL 0x204 ; - ; CALL 0x208 ; - ; RET
+        vm.load_rom(&[0x22, 0x04, 0x00, 0x00, 0x22, 0x08, 0x00, 0x00, 0x00, 0xEE]);
+        vm.run_once().unwrap();
+        vm.run_once().unwrap();
+        assert_eq!(vm.call_stack(), [0x202, 0x206]);
+        assert_eq!(vm.stack_depth(), 2);
+        assert!(format!("{vm:?}").contains("--- Stack ---\n[202, 206]"));
+        vm.run_once().unwrap();
+        assert_eq!(vm.call_stack(), [0x202]);
+    }
+
+    #[test]
+    fn call_then_return() {
+        let mut vm = test_vm();
+        // CALL 0x300 ; ADD V0, 1
+        vm.load_rom(&[0x23, 0x00, 0x70, 0x01]);
+        vm.ram[0x300..0x302].copy_from_slice(&[0x00, 0xEE]);
+        vm.run_once().unwrap();
+        assert_eq!(vm.registers.pc, 0x300);
+        assert_eq!(vm.call_stack(), [0x202]);
+        vm.run_once().unwrap();
+        assert_eq!(vm.registers.pc, 0x202);
+        assert_eq!(vm.stack_depth(), 0);
+        vm.run_once().unwrap();
+        assert_eq!(vm.registers.get(0), 1);
+        assert_eq!(vm.registers.pc, 0x204);
+    }
+
+    #[test]
+    fn step_over_calls() {
+        let mut vm = test_vm();
+        // CALL 0x300 ; ADD V0, 1
+        vm.load_rom(&[0x23, 0x00, 0x70, 0x01]);
+        // 0x300: CALL 0x310 ; CALL 0x310 ; RET
+        vm.ram[0x300..0x306].copy_from_slice(&[0x23, 0x10, 0x23, 0x10, 0x00, 0xEE]);
+        // 0x310: ADD V1, 1 ; RET
+        vm.ram[0x310..0x314].copy_from_slice(&[0x71, 0x01, 0x00, 0xEE]);
+
+        assert!(vm.step_over().unwrap());
+        assert_eq!(vm.registers.pc, 0x202);
+        assert_eq!(vm.registers.get(1), 2);
+        assert_eq!(vm.cycles(), 8);
+        assert!(vm.step_over().unwrap());
+        assert_eq!(vm.registers.pc, 0x204);
+
+        // Never returns
+        vm.ram[0x204..0x208].copy_from_slice(&[0x23, 0x20, 0x00, 0x00]);
+        vm.ram[0x320..0x322].copy_from_slice(&[0x13, 0x20]);
+        assert!(!vm.step_over().unwrap());
+    }
+
+    #[test]
+    fn address_overflow() {
+        for wrap_addresses in [false, true] {
+            let mut vm = test_vm();
+            vm.options.wrap_addresses = wrap_addresses;
+            // LD V0, 1 at the last address
+            vm.ram[0xFFE..].copy_from_slice(&[0x60, 0x01]);
+            vm.registers.pc = 0xFFE;
+            let pc = vm.run_once();
+            vm.registers.i = 0xFFF;
+            vm.registers.set(1, 1);
+            let i = vm.execute(Chip8Instr::IncrI(1));
+            if wrap_addresses {
+                assert_eq!(pc, Ok(()));
+                assert_eq!(i, Ok(()));
+                assert_eq!(vm.registers.pc, 0x000);
+                assert_eq!(vm.registers.i, 0x000);
+            } else {
+                assert_eq!(pc, Err(Chip8Error::AddressOverflow(0x1000)));
+                assert_eq!(i, Err(Chip8Error::AddressOverflow(0x1000)));
+            }
+        }
+    }
+
+    #[test]
+    fn state_summary() {
+        let mut vm = test_vm();
+        vm.registers.i = 0x2ea;
+        vm.registers.set(0xF, 1);
+        vm.registers.set(1, 0x3f);
+        vm.timers.modify(|t| t.delay = 5);
+        vm.execute(Chip8Instr::Call(0x300)).unwrap();
+        assert_eq!(
+            vm.state_summary(),
+            "PC=0x300 I=0x2ea V0=00 V1=3f V2=00 V3=00 V4=00 V5=00 V6=00 V7=00 \
+             V8=00 V9=00 VA=00 VB=00 VC=00 VD=00 VE=00 VF=01 DT=05 ST=00 SP=1"
+        );
+    }
+
+    #[test]
+    fn load_bytes_at() {
+        let mut vm = test_vm();
+        vm.load_bytes_at(0x300, &[1, 2, 3]).unwrap();
+        assert_eq!(vm.read_mem_slice(0x2FF, 5), Some(&[0, 1, 2, 3, 0][..]));
+        vm.load_bytes_at(0xFFE, &[4, 5]).unwrap();
+        vm.load_bytes_at(0x40, &[0; 0x10]).unwrap();
+        vm.load_bytes_at(0x1F0, &[]).unwrap();
+
+        assert_eq!(
+            vm.load_bytes_at(0xFFE, &[1, 2, 3]),
+            Err(Chip8Error::OutOfBounds(0x1000))
+        );
+        assert_eq!(
+            vm.load_bytes_at(0x40, &[0; 0x11]),
+            Err(Chip8Error::WriteProtected(0x50))
+        );
+        assert_eq!(
+            vm.load_bytes_at(0x100, &[0; 0x10]),
+            Err(Chip8Error::WriteProtected(0x100))
+        );
+        assert_eq!(vm.read_mem(0x50), Some(0xF0));
+        assert_eq!(vm.read_mem_slice(0xFFE, 2), Some(&[4, 5][..]));
+
+        let mut vm = test_vm();
+        vm.options.allow_font_overwrite = true;
+        vm.load_bytes_at(0x50, &[0x90]).unwrap();
+        assert_eq!(vm.read_mem(0x50), Some(0x90));
+    }
+
+    #[test]
+    fn protect_reserved() {
+        for protect_reserved in [false, true] {
+            let mut vm = test_vm();
+            vm.options.protect_reserved = protect_reserved;
+            vm.registers.set(0, 0xFF);
+            vm.registers.i = Chip8VM::FONT_START as U12;
+            let save = vm.execute(Chip8Instr::Save(0));
+            vm.registers.i = 0x1FF;
+            let decimal = vm.execute(Chip8Instr::Decimal(0));
+            if protect_reserved {
+                assert_eq!(save, Err(Chip8Error::WriteProtected(0x50)));
+                assert_eq!(decimal, Err(Chip8Error::WriteProtected(0x1FF)));
+                assert_eq!(vm.read_mem(0x50), Some(0xF0));
+                assert_eq!(vm.read_mem_slice(0x1FF, 3), Some(&[0, 0, 0][..]));
+            } else {
+                assert_eq!(save, Ok(()));
+                assert_eq!(decimal, Ok(()));
+                assert_eq!(vm.read_mem(0x50), Some(0xFF));
+                assert_eq!(vm.read_mem_slice(0x1FF, 3), Some(&[2, 5, 5][..]));
+            }
+            vm.registers.i = 0x200;
+            vm.execute(Chip8Instr::Save(0)).unwrap();
+        }
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn halt_on_self_jump() {
+        let mut vm = test_vm();
+        vm.options.halt_on_self_jump = true;
+        // JP 0x202 ; JP 0x202
+        vm.load_rom(&[0x12, 0x02, 0x12, 0x02]);
+        vm.run_once().unwrap();
+        assert!(!vm.is_halted());
+        vm.run_once().unwrap();
+        assert!(vm.is_halted());
+        assert_eq!(vm.registers.pc, 0x202);
+
+        let mut vm = test_vm();
+        vm.options.halt_on_self_jump = true;
+        vm.load_rom(&[0x12, 0x00]);
+        assert_eq!(vm.run(), Ok(()));
+        assert!(vm.is_halted());
+
+        let mut vm = test_vm();
+        vm.load_rom(&[0x12, 0x00]);
+        vm.run_once().unwrap();
+        assert!(!vm.is_halted());
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn idle_sleep_rereads_timer() {
+        // LD V0, 5 ; LD DT, V0 ; loop: LD V0, DT ; SE V0, 0 ; JP loop ; end: JP end
+        let rom = [
+            0x60, 0x05, 0xF0, 0x15, 0xF0, 0x07, 0x30, 0x00, 0x12, 0x04, 0x12, 0x0A,
+        ];
+        for timing in [TimingModel::Frequency, TimingModel::CyclesPerFrame(20)] {
+            let mut vm = test_vm();
+            vm.options.idle_sleep = true;
+            vm.options.halt_on_self_jump = true;
+            vm.options.timing = timing;
+            vm.load_rom(&rom);
+            assert_eq!(vm.run(), Ok(()), "{timing:?}");
+            assert_eq!(vm.registers.pc, 0x20A, "{timing:?}");
+            // About one iteration per frame
+            assert!(vm.cycles() < 40, "{timing:?}: {}", vm.cycles());
+        }
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn idle_loops() {
+        let is_idle = |rom: &[u8], pc: U12| {
+            let mut vm = test_vm();
+            vm.load_rom(rom);
+            vm.registers.pc = pc;
+            vm.is_idle_loop()
+        };
+        // loop: LD V0, DT ; SE V0, 0 ; JP loop
+        assert!(is_idle(&[0xF0, 0x07, 0x30, 0x00, 0x12, 0x00], 0x204));
+        assert!(!is_idle(&[0xF0, 0x07, 0x30, 0x00, 0x12, 0x00], 0x202));
+        // loop: SKP V1 ; JP loop
+        assert!(is_idle(&[0xE1, 0x9E, 0x12, 0x00], 0x202));
+        assert!(is_idle(&[0x12, 0x00], 0x200));
+        // loop: ADD V0, 1 ; SE V0, 0 ; JP loop
+        assert!(!is_idle(&[0x70, 0x01, 0x30, 0x00, 0x12, 0x00], 0x204));
+        // Forward jump
+        assert!(!is_idle(&[0x12, 0x04, 0x00, 0x00, 0x00, 0x00], 0x200));
+    }
+
+    #[test]
+    fn vm_is_send() {
+        fn assert_send<T: Send>() {}
+        assert_send::<Chip8VM>();
+    }
+
+    #[test]
+    fn instructions_per_frame() {
+        let vm = Chip8VM::new(
+            Some(660),
+            None,
+            Some(Chip8VMOptions {
+                manual_timers: true,
+                ..Default::default()
+            }),
+        );
+        assert_eq!(vm.instructions_per_frame(), 11.);
+        let vm = Chip8VM::new(
+            Some(660),
+            None,
+            Some(Chip8VMOptions {
+                manual_timers: true,
+                timing: TimingModel::CyclesPerFrame(20),
+                ..Default::default()
+            }),
+        );
+        assert_eq!(vm.instructions_per_frame(), 20.);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn cycles_per_frame_run() {
+        let mut vm = test_vm();
+        vm.options.halt_on_self_jump = true;
+        vm.options.timing = TimingModel::CyclesPerFrame(4);
+        // LD V0, 10 ; LD DT, V0 ; ADD V1, 1 x 6 ; JP self
+        vm.load_rom(&[
+            0x60, 0x0A, 0xF0, 0x15, 0x71, 0x01, 0x71, 0x01, 0x71, 0x01, 0x71, 0x01, 0x71, 0x01,
+            0x71, 0x01, 0x12, 0x10,
+        ]);
+        vm.run().unwrap();
+        // 9 instructions: 2 full frames then the halt in the third one, each ticking the timers
+        assert_eq!(vm.cycles(), 9);
+        assert_eq!(vm.timers.get().delay, 7);
+    }
+
+    #[test]
+    fn display_text() {
+        let mut vm = test_vm();
+        vm.load_rom(include_bytes!("../ibm.ch8"));
+        for _ in 0..100 {
+            vm.run_once().unwrap();
+        }
+        let text = vm.display_to_string();
+        assert_eq!(text.lines().count(), 32);
+        assert!(text.lines().all(|l| l.len() == 64));
+        assert_eq!(
+            text.matches('#').count(),
+            vm.display.iter().flatten().filter(|&&p| p).count()
+        );
+
+        let mut copy = test_vm();
+        copy.display_from_string(&text).unwrap();
+        assert_eq!(copy.display, vm.display);
+        copy.display_from_string(text.trim_end()).unwrap();
+        assert_eq!(copy.display, vm.display);
+
+        let blank = ".".repeat(64) + "\n";
+        assert!(copy.display_from_string(&blank.repeat(31)).is_err());
+        assert!(copy.display_from_string(&blank.repeat(33)).is_err());
+        assert!(copy
+            .display_from_string(&(blank.repeat(31) + &"x".repeat(64)))
+            .is_err());
+        assert!(copy.display_from_string(&(blank.repeat(31) + "#")).is_err());
+        assert_eq!(copy.display, vm.display);
+    }
+
+    #[test]
+    fn try_load_rom() {
+        let mut vm = test_vm();
+        assert_eq!(vm.try_load_rom(&[1; 4096 - 512]), Ok(None));
+        assert_eq!(
+            vm.try_load_rom(&[2; 4096 - 511]),
+            Err(Chip8Error::RomTooBig(3585, 3584))
+        );
+        assert_eq!(vm.read_mem(0x200), Some(1));
+        assert_eq!(vm.try_load_rom(&[]), Ok(Some(RomWarning::TooSmall)));
+        assert_eq!(vm.try_load_rom(&[0x12]), Ok(Some(RomWarning::TooSmall)));
+        // CLS ; JP 0x200, swapped
+        let swapped = [0xE0, 0x00, 0x00, 0x12];
+        assert_eq!(vm.try_load_rom(&swapped), Ok(Some(RomWarning::ByteSwapped)));
+        vm.options.byte_swap = true;
+        assert_eq!(vm.try_load_rom(&swapped), Ok(None));
+        assert_eq!(
+            vm.read_mem_slice(0x200, 4),
+            Some(&[0x00, 0xE0, 0x12, 0x00][..])
+        );
+        assert_eq!(
+            vm.try_load_rom(&[0x12, 0x00, 0x34]),
+            Ok(Some(RomWarning::OddLength))
+        );
+        assert_eq!(vm.read_mem_slice(0x200, 3), Some(&[0x00, 0x12, 0x34][..]));
+        vm.options.byte_swap = false;
+        assert_eq!(
+            vm.try_load_rom(&[0x12, 0x00, 0x00]),
+            Ok(Some(RomWarning::OddLength))
+        );
+
+        let mut vm = Chip8VM::new(
+            None,
+            None,
+            Some(Chip8VMOptions {
+                rom_start: Some(0x600),
+                manual_timers: true,
+                ..Default::default()
+            }),
+        );
+        assert_eq!(vm.try_load_rom(&[0; 0xA00]), Ok(None));
+        assert_eq!(
+            vm.try_load_rom(&[0; 0xA02]),
+            Err(Chip8Error::RomTooBig(0xA02, 0xA00))
+        );
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn delay_polling_stress() {
+        let mut vm = Chip8VM::new(None, None, Some(Chip8VMOptions::default()));
+        vm.timers.modify(|t| t.delay = 6);
+        vm.pre_run();
+        let start = Instant::now();
+        let mut last = 6;
+        while last > 0 {
+            vm.execute(Chip8Instr::GetDelay(0)).unwrap();
+            let delay = vm.registers.get(0);
+            assert!(delay <= last, "the delay went from {last} to {delay}");
+            last = delay;
+            assert!(
+                start.elapsed() < Duration::from_secs(1),
+                "the delay is stuck"
+            );
+        }
+        // 6 ticks at 60Hz
+        assert!(start.elapsed() >= Duration::from_millis(80));
+    }
+
+    #[test]
+    fn decode_cache_self_modifying() {
+        let mut vm = Chip8VM::new(
+            None,
+            None,
+            Some(Chip8VMOptions {
+                manual_timers: true,
+                decode_cache: true,
+                ..Default::default()
+            }),
+        );
+        // loop: ADD V2, 1 ; JP loop
+        vm.load_rom(&[0x72, 0x01, 0x12, 0x00]);
+        let run = |vm: &mut Chip8VM| {
+            for _ in 0..2 {
+                vm.run_once().unwrap();
+            }
+            vm.registers.get(2)
+        };
+        assert_eq!(run(&mut vm), 1);
+        assert_eq!(run(&mut vm), 2);
+
+        // Second byte of the cached instruction: ADD V2, 3
+        vm.write_mem(0x201, 0x03).unwrap();
+        assert_eq!(run(&mut vm), 5);
+
+        // Written by the ROM: ADD V2, 0x10
+        vm.registers.i = 0x200;
+        vm.registers.set(0, 0x72);
+        vm.registers.set(1, 0x10);
+        vm.execute(Chip8Instr::Save(1)).unwrap();
+        assert_eq!(run(&mut vm), 0x15);
+
+        vm.load_bytes_at(0x200, &[0x72, 0x20]).unwrap();
+        assert_eq!(run(&mut vm), 0x35);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn present_rate_limit() {
+        struct Rows(Arc<Mutex<Vec<u32>>>);
+        impl Renderer for Rows {
+            fn render(&mut self, _display: &Display) {}
+            fn render_rows(&mut self, _display: &Display, _intensity: &Intensity, rows: u32) {
+                self.0.lock().unwrap().push(rows);
+            }
+        }
+        let mut vm = test_vm();
+        let rendered = Arc::new(Mutex::new(Vec::new()));
+        vm.set_renderer(Box::new(Rows(Arc::clone(&rendered))));
+
+        vm.display[0][0] = true;
+        vm.present();
+        vm.display[1][0] = true;
+        vm.present();
+        vm.display[2][0] = true;
+        vm.present();
+        // Buffers are up to date, the renderer only got the first present
+        assert!(vm.front_display()[2][0]);
+        assert_eq!(*rendered.lock().unwrap(), [0b001]);
+
+        thread::sleep(Duration::from_millis(20));
+        vm.display[3][0] = true;
+        vm.present();
+        assert_eq!(*rendered.lock().unwrap(), [0b001, 0b1110]);
+
+        // The last frame before the ROM stops drawing is rendered by the next frame
+        vm.display[4][0] = true;
+        vm.present();
+        assert_eq!(rendered.lock().unwrap().len(), 2);
+        thread::sleep(Duration::from_millis(20));
+        vm.frame();
+        assert_eq!(*rendered.lock().unwrap(), [0b001, 0b1110, 0b10000]);
+        vm.frame();
+        assert_eq!(rendered.lock().unwrap().len(), 3);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn present_fade_per_render() {
+        let mut vm = test_vm();
+        vm.options.phosphor_frames = 4;
+        struct Blank;
+        impl Renderer for Blank {
+            fn render(&mut self, _display: &Display) {}
+        }
+        vm.set_renderer(Box::new(Blank));
+        vm.display[0][0] = true;
+        vm.present();
+        vm.display[0][0] = false;
+        vm.present();
+        vm.present();
+        vm.present();
+        // Rate limited, still fully lit
+        assert_eq!(vm.intensity()[0][0], u8::MAX);
+        thread::sleep(Duration::from_millis(20));
+        vm.present();
+        assert_eq!(vm.intensity()[0][0], u8::MAX - u8::MAX.div_ceil(4));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn dirty_rows() {
+        struct Rows(Arc<Mutex<Vec<u32>>>);
+        impl Renderer for Rows {
+            fn render(&mut self, _display: &Display) {}
+            fn render_rows(&mut self, _display: &Display, _intensity: &Intensity, rows: u32) {
+                self.0.lock().unwrap().push(rows);
+            }
+        }
+        let mut vm = test_vm();
+        vm.options.phosphor_frames = 2;
+        let rendered = Arc::new(Mutex::new(Vec::new()));
+        vm.set_renderer(Box::new(Rows(Arc::clone(&rendered))));
+
+        vm.registers.set(1, 3);
+        vm.registers.i = vm.char_index(0);
+        vm.execute(Chip8Instr::Display(0, 1, 5)).unwrap();
+        vm.present_frame();
+        assert_eq!(vm.dirty_rows(), 0b1111_1000);
+        vm.present_frame();
+        assert_eq!(vm.dirty_rows(), 0);
+        // Drawing twice leaves the screen as it was
+        vm.execute(Chip8Instr::Display(0, 1, 5)).unwrap();
+        vm.execute(Chip8Instr::Display(0, 1, 5)).unwrap();
+        vm.present_frame();
+        assert_eq!(vm.dirty_rows(), 0);
+        // Faded out over two frames
+        vm.execute(Chip8Instr::Clear).unwrap();
+        vm.present_frame();
+        vm.present_frame();
+        vm.present_frame();
+        assert_eq!(
+            *rendered.lock().unwrap(),
+            [0b1111_1000, 0, 0, 0b1111_1000, 0b1111_1000, 0]
+        );
+    }
+
+    #[test]
+    fn set_freq() {
+        let mut vm = test_vm();
+        assert_eq!(vm.freq(), 700);
+        vm.set_freq(1200).unwrap();
+        assert_eq!(vm.freq(), 1200);
+        assert_eq!(vm.set_freq(0), Err(Chip8Error::InvalidFrequency(0)));
+        assert_eq!(
+            vm.set_freq(Chip8VM::MAX_FREQ + 1),
+            Err(Chip8Error::InvalidFrequency(Chip8VM::MAX_FREQ + 1))
+        );
+        assert_eq!(vm.freq(), 1200);
+
+        // Set directly, the sleep between instructions stays finite
+        vm.freq = 0;
+        assert_eq!(vm.freq(), Chip8VM::MIN_FREQ);
+        assert_eq!(vm.instructions_per_frame(), 1. / 60.);
+        vm.freq = u32::MAX;
+        assert_eq!(vm.freq(), Chip8VM::MAX_FREQ);
+    }
+
+    #[test]
+    fn event_log() {
+        let mut vm = test_vm();
+        vm.options.record_events = true;
+        // CLS ; LD V0, 10 ; LD F, V0 ; DRW V0, V0, 5 ; DRW V0, V0, 5 ; DRW V0, V0, 5
+        vm.load_rom(&[
+            0x00, 0xE0, 0x60, 0x0A, 0xF0, 0x29, 0xD0, 0x05, 0xD0, 0x05, 0xD0, 0x05,
+        ]);
+        for _ in 0..4 {
+            vm.run_once().unwrap();
+        }
+        vm.tick_timers();
+        vm.run_once().unwrap();
+        assert_eq!(
+            vm.take_events(),
+            [
+                VmEvent::Clear {
+                    frame: 0,
+                    pc: 0x200
+                },
+                VmEvent::Collision {
+                    frame: 1,
+                    pc: 0x208,
+                    x: 10,
+                    y: 10
+                }
+            ]
+        );
+        vm.run_once().unwrap();
+        assert_eq!(vm.take_events(), []);
+
+        let mut vm = test_vm();
+        vm.execute(Chip8Instr::Clear).unwrap();
+        assert_eq!(vm.take_events(), []);
+    }
+
+    #[test]
+    fn strict_invariants() {
+        let strict_vm = |rom: &[u8]| {
+            let mut vm = test_vm();
+            vm.options.strict = true;
+            vm.load_rom(rom);
+            vm
+        };
+
+        // JP 0x201
+        let mut vm = strict_vm(&[0x12, 0x01]);
+        assert_eq!(
+            vm.run_once(),
+            Err(Chip8Error::BrokenInvariant("PC is odd", 0x201))
+        );
+
+        // CALL 0x200
+        let mut vm = strict_vm(&[0x22, 0x00]);
+        for _ in 0..Chip8VM::STACK_LIMIT {
+            vm.run_once().unwrap();
+        }
+        assert_eq!(
+            vm.run_once(),
+            Err(Chip8Error::BrokenInvariant(
+                "stack deeper than STACK_LIMIT",
+                17
+            ))
+        );
+
+        let mut vm = strict_vm(&[]);
+        vm.registers.i = 0x1000;
+        assert_eq!(
+            vm.run_once(),
+            Err(Chip8Error::BrokenInvariant("I outside of the RAM", 0x1000))
+        );
+        vm.registers.i = 0;
+        vm.registers.pc = 0xFFF;
+        assert!(vm.check_invariants().is_err());
+        assert_eq!(
+            vm.run_once().map_err(|e| e.to_string()),
+            Err("broken invariant: PC is odd (got 0xfff)".to_string())
+        );
+    }
+
+    #[test]
+    fn peek_instruction() {
+        let mut vm = test_vm();
+        // LD V3, 0x42 ; DRW V1, V2, 5
+        vm.load_rom(&[0x63, 0x42, 0xD1, 0x25]);
+        assert_eq!(vm.peek_instruction(), (0x6342, Chip8Instr::Set(3, 0x42)));
+        assert_eq!(vm.peek_instruction(), (0x6342, Chip8Instr::Set(3, 0x42)));
+        assert_eq!(vm.registers.pc, 0x200);
+        assert_eq!(vm.cycles(), 0);
+        vm.registers.pc = 0x202;
+        assert_eq!(
+            vm.peek_instruction(),
+            (0xD125, Chip8Instr::Display(1, 2, 5))
+        );
+    }
+
+    #[test]
+    fn skip_unknown() {
+        let vm_with = |skip_unknown: bool, strict: bool| {
+            let mut vm = test_vm();
+            vm.options.skip_unknown = skip_unknown;
+            vm.options.strict = strict;
+            vm.options.record_events = true;
+            // SE VA, VB (with a 1 instead of 0 in the last nibble) ; LD V0, 1
+            vm.load_rom(&[0x5A, 0xB1, 0x60, 0x01]);
+            vm
+        };
+
+        for strict in [false, true] {
+            let mut vm = vm_with(true, strict);
+            vm.run_once().unwrap();
+            vm.run_once().unwrap();
+            assert_eq!(vm.registers.get(0), 1);
+            assert_eq!(vm.registers.pc, 0x204);
+            assert_eq!(vm.cycles(), 2);
+            assert_eq!(
+                vm.take_events(),
+                [VmEvent::UnknownOpcode {
+                    frame: 0,
+                    pc: 0x200,
+                    opcode: 0x5AB1
+                }]
+            );
+        }
+
+        for strict in [false, true] {
+            let mut vm = vm_with(false, strict);
+            assert_eq!(vm.run_once(), Err(Chip8Error::UnknownOpcode(0x5AB1)));
+            assert_eq!(vm.registers.pc, 0x200);
+        }
+    }
+
+    #[test]
+    fn return_without_call() {
+        let mut vm = test_vm();
+        // LD V0, 1 ; RET
+        vm.load_rom(&[0x60, 0x01, 0x00, 0xEE]);
+        vm.run_once().unwrap();
+        assert_eq!(vm.run_once(), Err(Chip8Error::StackUnderflow(0x202)));
+    }
+
+    #[test]
+    fn memory_instructions_out_of_ram() {
+        let mut vm = test_vm();
+        vm.registers.i = 0xFFE;
+        for instr in [
+            Chip8Instr::Decimal(0),
+            Chip8Instr::Save(2),
+            Chip8Instr::Load(2),
+        ] {
+            assert_eq!(vm.execute(instr), Err(Chip8Error::OutOfBounds(0x1000)));
+        }
+        vm.execute(Chip8Instr::Load(1)).unwrap();
+        vm.execute(Chip8Instr::Save(1)).unwrap();
+    }
+
+    #[test]
+    fn sprite_past_ram() {
+        let mut vm = test_vm();
+        vm.ram[0xFFE] = 0xFF;
+        vm.ram[0xFFF] = 0x81;
+        vm.registers.i = 0xFFE;
+        let draw = Chip8Instr::Display(0, 0, 5);
+        assert_eq!(vm.execute(draw), Err(Chip8Error::OutOfBounds(0x1000)));
+        assert_eq!(vm.display, Chip8VM::DISPLAY_EMPTY);
+
+        vm.options.wrap_addresses = true;
+        vm.execute(draw).unwrap();
+        assert_eq!(vm.display[0][..8], [true; 8]);
+        assert_eq!(
+            vm.display[1][..8],
+            [true, false, false, false, false, false, false, true]
+        );
+        assert!(vm.display[2..5].iter().all(|row| !row.contains(&true)));
+        assert_eq!(vm.registers.get(0xF), 0);
+        // Ending exactly at the end of the RAM is fine
+        vm.options.wrap_addresses = false;
+        vm.execute(Chip8Instr::Display(0, 0, 2)).unwrap();
+        assert_eq!(vm.display, Chip8VM::DISPLAY_EMPTY);
+    }
+
+    #[test]
+    fn sys_is_ignored() {
+        let mut vm = test_vm();
+        // SYS 0x123 ; LD V0, 1
+        vm.load_rom(&[0x01, 0x23, 0x60, 0x01]);
+        vm.display[3][4] = true;
+        vm.run_once().unwrap();
+        assert!(vm.display[3][4]);
+        assert_eq!(vm.registers.pc, 0x202);
+        vm.run_once().unwrap();
+        assert_eq!(vm.registers.get(0), 1);
+    }
+
+    #[test]
+    fn control_targets() {
+        let mut vm = test_vm();
+        // CALL 0x208 ; JP 0x200 ; JP V0, 0x300 ; LD V0, 1 ; RET
+        vm.load_rom(&[0x22, 0x08, 0x12, 0x00, 0xB3, 0x00, 0x60, 0x01, 0x00, 0xEE]);
+        assert_eq!(
+            vm.control_targets(0x200, 0x20A),
+            [
+                (0x200, TargetKind::Call, 0x208),
+                (0x202, TargetKind::Jump, 0x200),
+                (0x204, TargetKind::Dynamic, 0x300),
+            ]
+        );
+        assert_eq!(
+            vm.control_targets(0x202, 0x204),
+            [(0x202, TargetKind::Jump, 0x200)]
+        );
+        assert_eq!(vm.control_targets(0x204, 0x200), []);
+        assert_eq!(vm.control_targets(0xF00, 0xFFFF), []);
+    }
+
+    #[test]
+    fn display_size() {
+        let vm = test_vm();
+        assert_eq!(vm.display_size(), (64, 32));
+        assert_eq!(vm.display[0].len(), vm.display_size().0);
+    }
+
+    #[test]
+    fn memory_map() {
+        let mut vm = test_vm();
+        let region = |kind, start, end| MemRegion { kind, start, end };
+        assert_eq!(
+            vm.memory_map(),
+            [
+                region(MemRegionKind::Reserved, 0x000, 0x050),
+                region(MemRegionKind::Font, 0x050, 0x0A0),
+                region(MemRegionKind::BigFont, 0x0A0, 0x140),
+                region(MemRegionKind::Reserved, 0x140, 0x200),
+                region(MemRegionKind::Free, 0x200, 0x1000),
+            ]
+        );
+        vm.load_rom(include_bytes!("../ibm.ch8"));
+        let map = vm.memory_map();
+        assert_eq!(map[4], region(MemRegionKind::Rom, 0x200, 0x284));
+        assert_eq!(map[5], region(MemRegionKind::Free, 0x284, 0x1000));
+        let i = map.iter().find(|r| r.contains(0x22A)).unwrap();
+        assert_eq!(i.kind, MemRegionKind::Rom);
+    }
+
+    #[test]
+    fn set_index() {
+        let mut vm = test_vm();
+        vm.set_index(0x300).unwrap();
+        assert_eq!(vm.index(), 0x300);
+        vm.registers.set(0, 123);
+        vm.execute(Chip8Instr::Decimal(0)).unwrap();
+        assert_eq!(vm.ram[0x300..0x303], [1, 2, 3]);
+        assert_eq!(
+            vm.set_index(0x1000),
+            Err(Chip8Error::AddressOverflow(0x1000))
+        );
+        assert_eq!(vm.index(), 0x300);
+
+        vm.options.wrap_addresses = true;
+        vm.set_index(0x1234).unwrap();
+        assert_eq!(vm.index(), 0x234);
+    }
+
+    #[test]
+    fn step_and_continue() {
+        let mut vm = test_vm();
+        // 0x200: ADD V0, 1 ; ADD V1, 1 ; JP 0x200
+        vm.load_rom(&[0x70, 0x01, 0x71, 0x01, 0x12, 0x00]);
+        assert_eq!(vm.step(4), StopReason::Count);
+        assert_eq!((vm.registers.get(0), vm.registers.get(1)), (2, 1));
+        assert_eq!(vm.continue_to(0x204), StopReason::Reached);
+        assert_eq!(vm.registers.pc, 0x204);
+
+        vm.add_breakpoint(0x202);
+        assert_eq!(vm.step(10), StopReason::Breakpoint(0x202));
+        assert_eq!(vm.cycles(), 7);
+        // Leaving the breakpoint, then back to it on the next loop
+        assert_eq!(vm.continue_to(0x204), StopReason::Reached);
+        assert_eq!(vm.continue_to(0x100), StopReason::Breakpoint(0x202));
+        assert!(vm.remove_breakpoint(0x202));
+        assert!(vm.breakpoints().is_empty());
+        assert_eq!(vm.step(3), StopReason::Count);
+
+        vm.options.halt_on_self_jump = true;
+        vm.load_rom(&[0x12, 0x00]);
+        vm.registers.pc = 0x200;
+        assert_eq!(vm.continue_to(0x300), StopReason::Halted);
+
+        vm.load_rom(&[0x00, 0xEE]);
+        assert_eq!(
+            vm.step(1),
+            StopReason::Error(Chip8Error::StackUnderflow(0x200))
+        );
+    }
+
+    #[test]
+    fn odd_pc() {
+        for align_pc in [false, true] {
+            let mut vm = test_vm();
+            vm.options.align_pc = align_pc;
+            // JP 0x201, then from 0x201: SYS 0x100 ; LD V1, 0x2A
+            vm.load_rom(&[0x12, 0x01, 0x00, 0x61, 0x2A]);
+            vm.step(3);
+            if align_pc {
+                // Back to the jump
+                assert_eq!(vm.registers.pc, 0x201);
+                assert!(!vm.unaligned_fetch);
+            } else {
+                assert_eq!(vm.registers.pc, 0x205);
+                assert_eq!(vm.registers.get(1), 0x2A);
+                assert!(vm.unaligned_fetch);
+            }
+        }
+    }
+
+    #[test]
+    fn timer_accessors() {
+        let mut vm = test_vm();
+        vm.set_delay_timer(10);
+        vm.set_buzzer_timer(3);
+        assert_eq!(vm.delay_timer(), 10);
+        assert_eq!(vm.buzzer_timer(), 3);
+        vm.execute(Chip8Instr::GetDelay(4)).unwrap();
+        assert_eq!(vm.registers.get(4), 10);
+        vm.tick_timers();
+        assert_eq!((vm.delay_timer(), vm.buzzer_timer()), (9, 2));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn buzzer_notifications() {
+        let mut vm = test_vm();
+        let changes = Arc::new(Mutex::new(Vec::new()));
+        let log = Arc::clone(&changes);
+        vm.set_on_buzzer(Box::new(move |on| log.lock().unwrap().push(on)));
+        assert!(!vm.is_buzzing());
+        vm.registers.set(0, 2);
+        vm.execute(Chip8Instr::SetBuzzer(0)).unwrap();
+        assert!(vm.is_buzzing());
+        // Setting it again while it sounds is not a transition
+        vm.execute(Chip8Instr::SetBuzzer(0)).unwrap();
+        vm.tick_timers();
+        assert_eq!(*changes.lock().unwrap(), [true]);
+        vm.tick_timers();
+        assert!(!vm.is_buzzing());
+        vm.tick_timers();
+        assert_eq!(*changes.lock().unwrap(), [true, false]);
+    }
+
+    #[cfg(feature = "catch_panics")]
+    #[test]
+    fn panic_to_error() {
+        struct PanickingRng;
+        impl RngCore for PanickingRng {
+            fn next_u32(&mut self) -> u32 {
+                panic!("rng failure")
+            }
+            fn next_u64(&mut self) -> u64 {
+                panic!("rng failure")
+            }
+            fn fill_bytes(&mut self, _dest: &mut [u8]) {
+                panic!("rng failure")
+            }
+            fn try_fill_bytes(&mut self, _dest: &mut [u8]) -> Result<(), rand::Error> {
+                panic!("rng failure")
+            }
+        }
+        let mut vm = test_vm();
+        vm.set_rng(Box::new(PanickingRng));
+        // LD V0, 1 ; RND V1, 0xFF
+        vm.load_rom(&[0x60, 0x01, 0xC1, 0xFF]);
+        vm.run_once().unwrap();
+        assert_eq!(vm.run_once(), Err(Chip8Error::InternalPanic(0x202, 0xC1FF)));
+        assert_eq!(vm.registers.pc, 0x202);
+        assert_eq!(vm.registers.get(0), 1);
+    }
+
+    #[cfg(feature = "log")]
+    #[test]
+    fn log_records() {
+        struct Capture(Mutex<Vec<(log::Level, String)>>);
+        impl log::Log for Capture {
+            fn enabled(&self, _metadata: &log::Metadata) -> bool {
+                true
+            }
+            fn log(&self, record: &log::Record) {
+                // Only the records of this thread, the other tests run in parallel
+                if thread::current().name() == Some("tests::log_records") {
+                    let line = record.args().to_string();
+                    self.0.lock().unwrap().push((record.level(), line));
+                }
+            }
+            fn flush(&self) {}
+        }
+        static CAPTURE: Capture = Capture(Mutex::new(Vec::new()));
+        log::set_logger(&CAPTURE).unwrap();
+        log::set_max_level(log::LevelFilter::Trace);
+
+        // Logged without the `debug` option: JP 0x201 ; SYS ...
+        let mut vm = test_vm();
+        vm.load_rom(&[0x12, 0x01, 0x00]);
+        vm.run_once().unwrap();
+        vm.run_once().unwrap();
+        let records = CAPTURE.0.lock().unwrap();
+        assert!(records.contains(&(log::Level::Warn, "unaligned fetch at 0x201".into())));
+        assert!(records.contains(&(
+            log::Level::Debug,
+            "input (raw,decoded): 1201,Jump(513)".into()
+        )));
+    }
+
+    #[test]
+    fn packed_display() {
+        let mut vm = test_vm();
+        vm.display[0][0] = true;
+        vm.display[0][9] = true;
+        vm.display[0][15] = true;
+        vm.display[31][63] = true;
+        let packed = vm.display_packed();
+        assert_eq!(packed.len(), 256);
+        assert_eq!(packed[..3], [0b1000_0000, 0b0100_0001, 0]);
+        assert_eq!(packed[255], 1);
+        assert_eq!(packed.iter().map(|b| b.count_ones()).sum::<u32>(), 4);
+    }
+
+    #[test]
+    fn extension_opcodes() {
+        // Its sprites contain 00FF, they aren't reachable
+        assert!(detect_extensions(include_bytes!("../ibm.ch8")).is_empty());
+        // CLS ; SYS 0x0FA ; DRW V0, V1, 1
+        assert!(detect_extensions(&[0x00, 0xE0, 0x00, 0xFA, 0xD0, 0x11]).is_empty());
+        for (opcode, extension) in [
+            ([0x00, 0xFF], Extension::SuperChip),
+            ([0x00, 0xC4], Extension::SuperChip),
+            ([0xD0, 0x10], Extension::SuperChip),
+            ([0xF3, 0x85], Extension::SuperChip),
+            ([0x00, 0xD2], Extension::XoChip),
+            ([0x51, 0x22], Extension::XoChip),
+            ([0xF2, 0x01], Extension::XoChip),
+        ] {
+            // Jumped to past some data
+            let rom = [0x12, 0x04, 0x00, 0xFE, opcode[0], opcode[1]];
+            let detected: Vec<_> = detect_extensions(&rom).into_iter().collect();
+            assert_eq!(detected, [extension], "{opcode:02x?}");
+        }
+        // The address after F000 isn't decoded, 00FF there is not a SUPER-CHIP opcode
+        let rom = [0xF0, 0x00, 0x00, 0xFF, 0x00, 0xFE];
+        let detected: Vec<_> = detect_extensions(&rom).into_iter().collect();
+        assert_eq!(detected, [Extension::SuperChip, Extension::XoChip]);
+        assert_eq!(detected[1].profile(), Profile::XoChip);
+        assert_eq!(detect_extensions(&rom[..4]).len(), 1);
+        // Both branches of a skip: SE V0, 0 ; EXIT ; SE V1, V2 ; 5123
+        let rom = [0x30, 0x00, 0x00, 0xFD, 0x51, 0x20, 0x51, 0x23];
+        let detected: Vec<_> = detect_extensions(&rom).into_iter().collect();
+        assert_eq!(detected, [Extension::SuperChip, Extension::XoChip]);
+
+        let mut vm = test_vm();
+        assert_eq!(vm.try_load_rom(&[0x00, 0xFF]), Ok(None));
+        vm.options.detect_extensions = true;
+        assert_eq!(
+            vm.try_load_rom(&[0x00, 0xFF]),
+            Ok(Some(RomWarning::Extension(Extension::SuperChip)))
+        );
+        // The swapped bytes are scanned
+        vm.options.byte_swap = true;
+        assert_eq!(vm.try_load_rom(&[0xE0, 0x00, 0x00, 0xFF]), Ok(None));
+        assert_eq!(
+            vm.try_load_rom(&[0xE0, 0x00, 0xFF, 0x00]),
+            Ok(Some(RomWarning::Extension(Extension::SuperChip)))
+        );
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn reload_rom() {
+        let mut vm = test_vm();
+        assert_eq!(
+            vm.reload_rom_from_file().unwrap_err().kind(),
+            std::io::ErrorKind::NotFound
+        );
+        let path = std::env::temp_dir().join(format!("chip-8-reload-{}.ch8", std::process::id()));
+        // LD V0, 1 ; LD V1, 2
+        std::fs::write(&path, [0x60, 0x01, 0x61, 0x02]).unwrap();
+        vm.load_rom_from_file(path.to_str().unwrap());
+        vm.step(2);
+        assert_eq!((vm.registers.v0, vm.registers.v1), (1, 2));
+
+        // Smaller, the second instruction is gone
+        std::fs::write(&path, [0x60, 0x03]).unwrap();
+        assert_eq!(vm.reload_rom_from_file().unwrap(), None);
+        assert_eq!(vm.registers.pc, 0x200);
+        assert_eq!(vm.read_mem(0x202), Some(0));
+        vm.run_once().unwrap();
+        assert_eq!((vm.registers.v0, vm.registers.v1), (3, 0));
+
+        // Too big or removed, the running ROM is kept
+        std::fs::write(&path, vec![0x12; Chip8VM::RAM_SIZE]).unwrap();
+        let e = vm.reload_rom_from_file().unwrap_err();
+        assert_eq!(e.kind(), std::io::ErrorKind::InvalidData);
+        std::fs::remove_file(&path).unwrap();
+        assert!(vm.reload_rom_from_file().is_err());
+        assert_eq!((vm.registers.pc, vm.read_mem(0x200)), (0x202, Some(0x60)));
+    }
+
+    #[test]
+    fn reset_keeps_ram() {
+        let mut vm = test_vm();
+        // LD V0, 5 ; CALL 0x206 ; CLS ; DRW V0, V0, 5
+        vm.load_rom(&[0x60, 0x05, 0x22, 0x06, 0x00, 0xE0, 0xD0, 0x05]);
+        vm.step(3);
+        vm.set_delay_timer(10);
+        vm.reset();
+        assert_eq!((vm.registers.pc, vm.registers.v0), (0x200, 0));
+        assert!(vm.stack.is_empty());
+        assert_eq!(vm.delay_timer(), 0);
+        assert!(vm.display.iter().flatten().all(|&on| !on));
+        assert_eq!(vm.read_mem(0x206), Some(0xD0));
+    }
+
     #[test]
     fn parse_instructions() {
         let tests: Vec<(u16, Chip8Instr)> = vec![
             (0x00E0, Chip8Instr::Clear),
+            (0x0123, Chip8Instr::Sys(0x123)),
+            (0x0000, Chip8Instr::Sys(0)),
+            (0x00E1, Chip8Instr::Sys(0x0E1)),
             (0x00EE, Chip8Instr::Return),
             (0x1245, Chip8Instr::Jump(0x245)),
             (0x1EF3, Chip8Instr::Jump(0xEF3)),
+            (0x3A12, Chip8Instr::SkipEqImm(0xA, 0x12)),
+            (0x4A12, Chip8Instr::SkipNeImm(0xA, 0x12)),
+            (0x5AB0, Chip8Instr::SkipEqReg(0xA, 0xB)),
+            (0x9AB0, Chip8Instr::SkipNeReg(0xA, 0xB)),
+            (0x5AB1, Chip8Instr::Unknown),
+            (0x9AB8, Chip8Instr::Unknown),
+            (0x8AB4, Chip8Instr::ArithmOp(0xA, 0xB, 4)),
+            (0x8AB8, Chip8Instr::Unknown),
+            (0xE19E, Chip8Instr::KeyDown(0x1)),
+            (0xE2A1, Chip8Instr::KeyUp(0x2)),
+            (0xE2A2, Chip8Instr::Unknown),
+            (0xF429, Chip8Instr::Char(0x4)),
+            (0xF430, Chip8Instr::BigChar(0x4)),
             (0x6336, Chip8Instr::Set(0x3, 0x36)),
             (0x6F4A, Chip8Instr::Set(0xF, 0x4A)),
             (0x7336, Chip8Instr::Add(0x3, 0x36)),
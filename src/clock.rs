@@ -0,0 +1,139 @@
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::Chip8VM;
+
+// Time source of the timer thread, see `Chip8VM::set_clock`
+pub trait Clock: Send + Sync {
+    fn now(&self) -> Instant;
+    // May return early, the caller checks `now` again
+    fn sleep(&self, duration: Duration);
+}
+
+// The wall clock, used by default
+pub struct SystemClock;
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn sleep(&self, duration: Duration) {
+        thread::sleep(duration);
+    }
+}
+
+// Clock moved forward by the test instead of the wall clock, so the timers can be
+// checked without real sleeps. `advance` lets the sleeping thread (only one per clock)
+// run through the given time, `now` moves as its sleeps complete.
+pub struct MockClock {
+    state: Mutex<MockState>,
+    cvar: Condvar,
+}
+struct MockState {
+    now: Instant,
+    // Advanced but not slept through yet
+    budget: Duration,
+    // Duration the sleeping thread waits for
+    sleeping: Option<Duration>,
+}
+
+impl MockClock {
+    // How long a sleep waits for `advance` before returning early, so the thread can
+    // notice it must stop
+    const POLL: Duration = Duration::from_millis(10);
+
+    pub fn new() -> Self {
+        MockClock {
+            state: Mutex::new(MockState {
+                now: Instant::now(),
+                budget: Duration::ZERO,
+                sleeping: None,
+            }),
+            cvar: Condvar::new(),
+        }
+    }
+
+    // Returns once the sleeping thread went through `duration` and waits for more time.
+    // Never returns if nothing sleeps on this clock (e.g. the timers are not running).
+    pub fn advance(&self, duration: Duration) {
+        let mut state = self.state.lock().unwrap();
+        state.budget += duration;
+        self.cvar.notify_all();
+        let _state = self
+            .cvar
+            .wait_while(
+                state,
+                |state| !matches!(state.sleeping, Some(sleep) if sleep > state.budget),
+            )
+            .unwrap();
+    }
+}
+
+impl Default for MockClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> Instant {
+        self.state.lock().unwrap().now
+    }
+
+    fn sleep(&self, duration: Duration) {
+        let mut state = self.state.lock().unwrap();
+        state.sleeping = Some(duration);
+        self.cvar.notify_all();
+        let (mut state, timeout) = self
+            .cvar
+            .wait_timeout_while(state, Self::POLL, |state| state.budget < duration)
+            .unwrap();
+        state.sleeping = None;
+        if !timeout.timed_out() {
+            state.budget -= duration;
+            state.now += duration;
+        }
+        self.cvar.notify_all();
+    }
+}
+
+impl Chip8VM {
+    // Replaces the time source of the timer thread (`SystemClock` by default), e.g. with
+    // a `MockClock`. Without effect when the timers are driven manually.
+    pub fn set_clock(&mut self, clock: Arc<dyn Clock>) {
+        self.timers.set_clock(clock, self.options.debug);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Chip8VMOptions;
+
+    #[test]
+    fn mock_clock_drives_timers() {
+        let clock = Arc::new(MockClock::new());
+        let mut vm = Chip8VM::new(
+            None,
+            None,
+            Some(Chip8VMOptions {
+                hide_display: true,
+                ..Default::default()
+            }),
+        );
+        vm.set_clock(clock.clone());
+        vm.timers.modify(|t| {
+            t.delay = 100;
+            t.buzzer = 30;
+        });
+        vm.timers.start();
+        let start = clock.now();
+        clock.advance(Duration::from_secs(1));
+        let timers = vm.timers.get();
+        assert_eq!((timers.delay, timers.buzzer), (40, 0));
+        assert!(clock.now() - start <= Duration::from_secs(1));
+        clock.advance(Duration::from_secs(1) / 2);
+        assert_eq!(vm.timers.get().delay, 10);
+    }
+}
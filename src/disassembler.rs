@@ -0,0 +1,264 @@
+use alloc::collections::BTreeMap;
+use alloc::{format, string::String, vec};
+use core::fmt::Write;
+
+use crate::{Chip8Instr, Chip8VM};
+
+// Disassembles a ROM loaded at 0x200, in the syntax accepted by `assemble`.
+// Only the bytes reachable from the entry point (following jumps, calls and skips)
+// are decoded as instructions, the rest is listed as data with `DW`/`DB`.
+// Jump and call targets get labels (`sub_` for subroutines, `loc_` otherwise).
+pub fn disassemble(rom: &[u8]) -> String {
+    listing(rom, false)
+}
+
+// Same listing with the address and bytes of each line in a trailing comment,
+// e.g. `    CLS                     ; 200: 00e0`, it still assembles to `rom`
+pub fn disassemble_annotated(rom: &[u8]) -> String {
+    listing(rom, true)
+}
+
+fn listing(rom: &[u8], annotate: bool) -> String {
+    let base = Chip8VM::RAM_ROM_START;
+    let opcode_at = |addr: usize| -> Option<u16> {
+        let offset = addr.checked_sub(base)?;
+        Some(u16::from_be_bytes([
+            *rom.get(offset)?,
+            *rom.get(offset + 1)?,
+        ]))
+    };
+    let in_rom = |addr: usize| addr >= base && addr < base + rom.len();
+
+    //Reachability pass
+    let mut code = vec![false; rom.len()];
+    let mut labels: BTreeMap<usize, &str> = BTreeMap::new();
+    let mut pending = vec![base];
+    while let Some(addr) = pending.pop() {
+        let Some(opcode) = opcode_at(addr) else {
+            continue;
+        };
+        let instr = Chip8Instr::from(opcode);
+        if code[addr - base] || canonical(instr) != Some(opcode) {
+            continue;
+        }
+        code[addr - base] = true;
+        match instr {
+            Chip8Instr::Jump(nnn) => {
+                labels.entry(nnn as usize).or_insert("loc");
+                pending.push(nnn as usize);
+            }
+            Chip8Instr::Call(nnn) => {
+                labels.insert(nnn as usize, "sub");
+                pending.extend([nnn as usize, addr + 2]);
+            }
+            // The offset is only known at run time, the table start is the best guess
+            Chip8Instr::JumpOff(nnn) => {
+                labels.entry(nnn as usize).or_insert("loc");
+                pending.push(nnn as usize);
+            }
+            Chip8Instr::Return => {}
+            Chip8Instr::SkipEqImm(..)
+            | Chip8Instr::SkipNeImm(..)
+            | Chip8Instr::SkipEqReg(..)
+            | Chip8Instr::SkipNeReg(..)
+            | Chip8Instr::KeyUp(_)
+            | Chip8Instr::KeyDown(_) => pending.extend([addr + 2, addr + 4]),
+            _ => pending.push(addr + 2),
+        }
+    }
+    labels.retain(|&addr, _| in_rom(addr));
+    let label = |addr: u16| match labels.get(&(addr as usize)) {
+        Some(prefix) => format!("{prefix}_{addr:03x}"),
+        None => format!("{addr:#05x}"),
+    };
+
+    //Listing
+    let mut listing = String::new();
+    let mut offset = 0;
+    // An instruction whose second byte starts another instruction (or has a label) is
+    // listed as data, so that both can be assembled back
+    let is_start = |o: usize| code.get(o) == Some(&true) || labels.contains_key(&(base + o));
+    let line = |listing: &mut String, offset: usize, len: usize, text: String| {
+        if annotate {
+            let bytes = &rom[offset..offset + len];
+            let hex = bytes
+                .iter()
+                .fold(String::new(), |hex, b| hex + &format!("{b:02x}"));
+            let _ = writeln!(listing, "    {text:<24}; {:03x}: {hex}", base + offset);
+        } else {
+            let _ = writeln!(listing, "    {text}");
+        }
+    };
+    while offset < rom.len() {
+        let addr = base + offset;
+        if let Some(prefix) = labels.get(&addr) {
+            let _ = writeln!(listing, "{prefix}_{addr:03x}:");
+        }
+        if code[offset] && !is_start(offset + 1) {
+            let instr = Chip8Instr::from(opcode_at(addr).unwrap_or_default());
+            line(&mut listing, offset, 2, mnemonic(instr, &label));
+            offset += 2;
+        } else if offset + 1 < rom.len() && !is_start(offset + 1) {
+            let word = u16::from_be_bytes([rom[offset], rom[offset + 1]]);
+            line(&mut listing, offset, 2, format!("DW {word:#06x}"));
+            offset += 2;
+        } else {
+            line(&mut listing, offset, 1, format!("DB {:#04x}", rom[offset]));
+            offset += 1;
+        }
+    }
+    listing
+}
+
+// Opcode that `assemble` produces for this instruction, None for the ones with no
+// mnemonic. Opcodes decoding to an instruction with a different canonical form
+// (e.g. 0x5XY1) are shown as data.
+fn canonical(instr: Chip8Instr) -> Option<u16> {
+    let xy = |base: u16, x: u8, y: u8| base | (x as u16) << 8 | (y as u16) << 4;
+    let xnn = |base: u16, x: u8, nn: u8| base | (x as u16) << 8 | nn as u16;
+    let opcode = match instr {
+        Chip8Instr::Sys(nnn) => nnn,
+        Chip8Instr::Clear => 0x00E0,
+        Chip8Instr::Return => 0x00EE,
+        Chip8Instr::Jump(nnn) => 0x1000 | nnn,
+        Chip8Instr::Call(nnn) => 0x2000 | nnn,
+        Chip8Instr::SkipEqImm(x, nn) => xnn(0x3000, x, nn),
+        Chip8Instr::SkipNeImm(x, nn) => xnn(0x4000, x, nn),
+        Chip8Instr::SkipEqReg(x, y) => xy(0x5000, x, y),
+        Chip8Instr::Set(x, nn) => xnn(0x6000, x, nn),
+        Chip8Instr::Add(x, nn) => xnn(0x7000, x, nn),
+        Chip8Instr::SetR(x, y) => xy(0x8000, x, y),
+        Chip8Instr::BitOp(x, y, op) | Chip8Instr::ShiftOp(x, y, op) => xy(0x8000, x, y) | op as u16,
+        Chip8Instr::ArithmOp(x, y, op @ (4 | 5 | 7)) => xy(0x8000, x, y) | op as u16,
+        Chip8Instr::ArithmOp(..) => return None,
+        Chip8Instr::SkipNeReg(x, y) => xy(0x9000, x, y),
+        Chip8Instr::SetI(nnn) => 0xA000 | nnn,
+        Chip8Instr::JumpOff(nnn) => 0xB000 | nnn,
+        Chip8Instr::Rand(x, nn) => xnn(0xC000, x, nn),
+        Chip8Instr::Display(x, y, n) => xy(0xD000, x, y) | n as u16,
+        Chip8Instr::KeyDown(x) => xnn(0xE000, x, 0x9E),
+        Chip8Instr::KeyUp(x) => xnn(0xE000, x, 0xA1),
+        Chip8Instr::GetDelay(x) => xnn(0xF000, x, 0x07),
+        Chip8Instr::GetKey(x) => xnn(0xF000, x, 0x0A),
+        Chip8Instr::SetDelay(x) => xnn(0xF000, x, 0x15),
+        Chip8Instr::SetBuzzer(x) => xnn(0xF000, x, 0x18),
+        Chip8Instr::IncrI(x) => xnn(0xF000, x, 0x1E),
+        Chip8Instr::Char(x) => xnn(0xF000, x, 0x29),
+        Chip8Instr::BigChar(x) => xnn(0xF000, x, 0x30),
+        Chip8Instr::Decimal(x) => xnn(0xF000, x, 0x33),
+        Chip8Instr::Save(x) => xnn(0xF000, x, 0x55),
+        Chip8Instr::Load(x) => xnn(0xF000, x, 0x65),
+        Chip8Instr::Unknown => return None,
+    };
+    Some(opcode)
+}
+
+fn mnemonic(instr: Chip8Instr, label: &impl Fn(u16) -> String) -> String {
+    match instr {
+        Chip8Instr::Sys(nnn) => format!("SYS {nnn:#05x}"),
+        Chip8Instr::Clear => "CLS".into(),
+        Chip8Instr::Return => "RET".into(),
+        Chip8Instr::Jump(nnn) => format!("JP {}", label(nnn)),
+        Chip8Instr::Call(nnn) => format!("CALL {}", label(nnn)),
+        Chip8Instr::SkipEqImm(x, nn) => format!("SE V{x:X}, {nn:#04x}"),
+        Chip8Instr::SkipNeImm(x, nn) => format!("SNE V{x:X}, {nn:#04x}"),
+        Chip8Instr::SkipEqReg(x, y) => format!("SE V{x:X}, V{y:X}"),
+        Chip8Instr::Set(x, nn) => format!("LD V{x:X}, {nn:#04x}"),
+        Chip8Instr::Add(x, nn) => format!("ADD V{x:X}, {nn:#04x}"),
+        Chip8Instr::SetR(x, y) => format!("LD V{x:X}, V{y:X}"),
+        Chip8Instr::BitOp(x, y, op) | Chip8Instr::ArithmOp(x, y, op) => {
+            let name = match op {
+                1 => "OR",
+                2 => "AND",
+                3 => "XOR",
+                4 => "ADD",
+                5 => "SUB",
+                _ => "SUBN",
+            };
+            format!("{name} V{x:X}, V{y:X}")
+        }
+        Chip8Instr::ShiftOp(x, y, op) => {
+            let name = if op == 6 { "SHR" } else { "SHL" };
+            format!("{name} V{x:X}, V{y:X}")
+        }
+        Chip8Instr::SkipNeReg(x, y) => format!("SNE V{x:X}, V{y:X}"),
+        Chip8Instr::SetI(nnn) => format!("LD I, {nnn:#05x}"),
+        Chip8Instr::JumpOff(nnn) => format!("JP V0, {}", label(nnn)),
+        Chip8Instr::Rand(x, nn) => format!("RND V{x:X}, {nn:#04x}"),
+        Chip8Instr::Display(x, y, n) => format!("DRW V{x:X}, V{y:X}, {n}"),
+        Chip8Instr::KeyDown(x) => format!("SKP V{x:X}"),
+        Chip8Instr::KeyUp(x) => format!("SKNP V{x:X}"),
+        Chip8Instr::GetDelay(x) => format!("LD V{x:X}, DT"),
+        Chip8Instr::GetKey(x) => format!("LD V{x:X}, K"),
+        Chip8Instr::SetDelay(x) => format!("LD DT, V{x:X}"),
+        Chip8Instr::SetBuzzer(x) => format!("LD ST, V{x:X}"),
+        Chip8Instr::IncrI(x) => format!("ADD I, V{x:X}"),
+        Chip8Instr::Char(x) => format!("LD F, V{x:X}"),
+        Chip8Instr::BigChar(x) => format!("LD HF, V{x:X}"),
+        Chip8Instr::Decimal(x) => format!("LD B, V{x:X}"),
+        Chip8Instr::Save(x) => format!("LD [I], V{x:X}"),
+        Chip8Instr::Load(x) => format!("LD V{x:X}, [I]"),
+        Chip8Instr::Unknown => unreachable!("unknown opcodes are listed as data"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assembler::assemble;
+
+    #[test]
+    fn code_and_data() {
+        let source = "
+            CALL draw
+        loop:
+            SKP V0
+            JP loop
+            JP V0, table
+        draw:
+            LD I, sprite
+            DRW V0, V1, 2
+            RET
+        table:
+            CLS
+        sprite:
+            DB 0xFF, 0x81
+            DB 0x12
+        ";
+        let rom = assemble(source).unwrap();
+        let listing = disassemble(&rom);
+        assert!(listing.contains("CALL sub_208\n"));
+        assert!(listing.contains("loc_202:\n    SKP V0\n    JP loc_202\n"));
+        assert!(listing.contains("JP V0, loc_20e\n"));
+        assert!(listing.contains("    DW 0xff81\n    DB 0x12\n"));
+        assert_eq!(assemble(&listing).unwrap(), rom);
+    }
+
+    #[test]
+    fn round_trip_roms() {
+        for rom in [
+            &include_bytes!("../ibm.ch8")[..],
+            include_bytes!("../test_opcode.ch8"),
+            include_bytes!("../bc_test.ch8"),
+            include_bytes!("../KALEID.ch8"),
+        ] {
+            let listing = disassemble(rom);
+            assert_eq!(assemble(&listing).unwrap(), rom, "{listing}");
+            let listing = disassemble_annotated(rom);
+            assert_eq!(assemble(&listing).unwrap(), rom, "{listing}");
+        }
+    }
+
+    #[test]
+    fn annotated() {
+        // CLS ; JP 0x200 ; data
+        let listing = disassemble_annotated(&[0x00, 0xE0, 0x12, 0x00, 0xAB]);
+        assert_eq!(
+            listing,
+            "loc_200:\n\
+            \x20   CLS                     ; 200: 00e0\n\
+            \x20   JP loc_200              ; 202: 1200\n\
+            \x20   DB 0xab                 ; 204: ab\n"
+        );
+    }
+}
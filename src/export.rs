@@ -0,0 +1,142 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use crate::{pack_display, Chip8VM, Display};
+
+impl Chip8VM {
+    // Writes the display to a binary portable bitmap (P4), 1 for a lit pixel
+    pub fn save_pbm(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let mut out = BufWriter::new(File::create(path)?);
+        write_pbm(&self.display, &mut out, false)?;
+        out.flush()
+    }
+
+    // Same as `save_pbm` in the plain text variant (P1)
+    pub fn save_plain_pbm(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let mut out = BufWriter::new(File::create(path)?);
+        write_pbm(&self.display, &mut out, true)?;
+        out.flush()
+    }
+
+    // Writes the display as an X BitMap, C source defining `<name>_width`,
+    // `<name>_height` and `<name>_bits` where the name is the file name without extension
+    pub fn save_xbm(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let path = path.as_ref();
+        let name = xbm_name(path);
+        let mut out = BufWriter::new(File::create(path)?);
+        write_xbm(&self.display, &mut out, &name)?;
+        out.flush()
+    }
+}
+
+// Portable bitmap of `display`, in text (P1) when `plain` else binary (P4)
+pub fn write_pbm(display: &Display, out: &mut impl Write, plain: bool) -> io::Result<()> {
+    let (width, height) = (display[0].len(), display.len());
+    if !plain {
+        write!(out, "P4\n{width} {height}\n")?;
+        return out.write_all(&pack_display(display));
+    }
+    write!(out, "P1\n{width} {height}\n")?;
+    for row in display {
+        // Lines stay under the 70 characters PBM allows
+        for pixels in row.chunks(32) {
+            let line: String = pixels
+                .iter()
+                .map(|&on| if on { '1' } else { '0' })
+                .collect();
+            writeln!(out, "{line}")?;
+        }
+    }
+    Ok(())
+}
+
+// XBM stores the leftmost pixel of each byte in the least significant bit
+pub fn write_xbm(display: &Display, out: &mut impl Write, name: &str) -> io::Result<()> {
+    let (width, height) = (display[0].len(), display.len());
+    writeln!(out, "#define {name}_width {width}")?;
+    writeln!(out, "#define {name}_height {height}")?;
+    writeln!(out, "static unsigned char {name}_bits[] = {{")?;
+    let bytes: Vec<u8> = pack_display(display)
+        .into_iter()
+        .map(u8::reverse_bits)
+        .collect();
+    for line in bytes.chunks(12) {
+        let line: Vec<String> = line.iter().map(|byte| format!("{byte:#04x}")).collect();
+        writeln!(out, "   {},", line.join(", "))?;
+    }
+    writeln!(out, "}};")
+}
+
+// A C identifier from the file name
+fn xbm_name(path: &Path) -> String {
+    let stem = path
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or("");
+    let name: String = stem
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    match name.chars().next() {
+        Some(c) if !c.is_ascii_digit() => name,
+        _ => format!("chip8_{name}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::test_vm;
+
+    fn vm() -> Chip8VM {
+        let mut vm = test_vm();
+        vm.display[0][0] = true;
+        vm.display[0][9] = true;
+        vm.display[31][63] = true;
+        vm
+    }
+
+    #[test]
+    fn pbm_files() {
+        let dir = std::env::temp_dir();
+        let path = dir.join(format!("chip-8-frame-{}.pbm", std::process::id()));
+        let vm = vm();
+
+        vm.save_pbm(&path).unwrap();
+        let bytes = std::fs::read(&path).unwrap();
+        let header = b"P4\n64 32\n";
+        assert_eq!(bytes[..header.len()], *header);
+        let raster = &bytes[header.len()..];
+        assert_eq!(raster, vm.display_packed());
+
+        vm.save_plain_pbm(&path).unwrap();
+        let text = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let mut words = text.split_whitespace();
+        assert_eq!(words.next(), Some("P1"));
+        let width: usize = words.next().unwrap().parse().unwrap();
+        let height: usize = words.next().unwrap().parse().unwrap();
+        assert_eq!((width, height), (64, 32));
+        let pixels: String = words.collect();
+        assert_eq!(pixels.len(), 64 * 32);
+        assert_eq!(&pixels[..10], "1000000001");
+        assert!(pixels.ends_with("01"));
+        assert_eq!(pixels.matches('1').count(), 3);
+    }
+
+    #[test]
+    fn xbm_file() {
+        let path = std::env::temp_dir().join(format!("frame-{}.xbm", std::process::id()));
+        vm().save_xbm(&path).unwrap();
+        let text = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let name = format!("frame_{}", std::process::id());
+        assert!(text.starts_with(&format!(
+            "#define {name}_width 64\n#define {name}_height 32\nstatic unsigned char {name}_bits[] = {{\n   0x01, 0x02, 0x00,"
+        )));
+        assert!(text.ends_with(", 0x80,\n};\n"));
+        assert_eq!(text.matches("0x").count(), 256);
+        assert_eq!(xbm_name(Path::new("/tmp/2-frame.xbm")), "chip8_2_frame");
+    }
+}
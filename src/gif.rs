@@ -0,0 +1,175 @@
+use std::io::{self, Write};
+use std::time::{Duration, Instant};
+
+use ::gif::{Encoder, Frame, Repeat};
+
+use crate::{Chip8VM, Display, Renderer};
+
+// Records the rendered frames and encodes them as an animated GIF on `finish`.
+// To keep a handle on it once it is given to the VM, share it through
+// `Arc<Mutex<GifRecorder>>` (which is a `Renderer` too).
+pub struct GifRecorder {
+    scale: u16,
+    // Frames rendered sooner than this after the previous one are skipped
+    min_interval: Duration,
+    // Frames past this limit are dropped to bound memory usage
+    max_frames: usize,
+    frames: Vec<(Display, Instant)>,
+    // Latest skipped frame, written on `finish` so the recording ends on the final screen
+    pending: Option<Display>,
+}
+
+impl GifRecorder {
+    pub const DEFAULT_MAX_FPS: u32 = 30;
+    // About 10 minutes at 30 fps, 2kB per frame
+    pub const DEFAULT_MAX_FRAMES: usize = 18_000;
+    // How long the last frame stays on screen before the animation loops
+    const LAST_FRAME_DELAY: u16 = 100;
+
+    pub fn new(scale: u16) -> Self {
+        GifRecorder {
+            scale: scale.max(1),
+            min_interval: Duration::from_secs(1) / Self::DEFAULT_MAX_FPS,
+            max_frames: Self::DEFAULT_MAX_FRAMES,
+            frames: Vec::new(),
+            pending: None,
+        }
+    }
+
+    // 0 disables the rate cap
+    pub fn with_max_fps(mut self, fps: u32) -> Self {
+        self.min_interval = match fps {
+            0 => Duration::ZERO,
+            fps => Duration::from_secs(1) / fps,
+        };
+        self
+    }
+
+    pub fn with_max_frames(mut self, max_frames: usize) -> Self {
+        self.max_frames = max_frames.max(1);
+        self
+    }
+
+    pub fn frame_count(&self) -> usize {
+        self.frames.len() + self.pending.is_some() as usize
+    }
+
+    // Writes the recording to `out` and gives it back
+    pub fn finish<W: Write>(mut self, out: W) -> io::Result<W> {
+        if let Some(display) = self.pending.take() {
+            let at = self
+                .frames
+                .last()
+                .map_or_else(Instant::now, |(_, t)| *t + self.min_interval);
+            self.frames.push((display, at));
+        }
+
+        let width = (Chip8VM::DISPLAY_WIDTH as u16) * self.scale;
+        let height = (Chip8VM::DISPLAY_HEIGHT as u16) * self.scale;
+        let mut encoder = Encoder::new(out, width, height, &[0, 0, 0, 255, 255, 255])
+            .map_err(io::Error::other)?;
+        encoder
+            .set_repeat(Repeat::Infinite)
+            .map_err(io::Error::other)?;
+        for (n, (display, at)) in self.frames.iter().enumerate() {
+            let mut frame = Frame::from_indexed_pixels(width, height, self.pixels(display), None);
+            frame.delay = match self.frames.get(n + 1) {
+                // GIF delays are in hundredths of a second, viewers ignore values under 2
+                Some((_, next)) => {
+                    ((*next - *at).as_millis() / 10).clamp(2, u16::MAX as u128) as u16
+                }
+                None => Self::LAST_FRAME_DELAY,
+            };
+            encoder.write_frame(&frame).map_err(io::Error::other)?;
+        }
+        encoder.into_inner()
+    }
+
+    // One palette index per upscaled pixel
+    fn pixels(&self, display: &Display) -> Vec<u8> {
+        let scale = self.scale as usize;
+        let mut pixels =
+            Vec::with_capacity(Chip8VM::DISPLAY_WIDTH * Chip8VM::DISPLAY_HEIGHT * scale * scale);
+        for row in display {
+            for _ in 0..scale {
+                for &pixel in row {
+                    pixels.extend(core::iter::repeat_n(pixel as u8, scale));
+                }
+            }
+        }
+        pixels
+    }
+}
+
+impl Renderer for GifRecorder {
+    fn render(&mut self, display: &Display) {
+        if self.frames.len() >= self.max_frames {
+            return;
+        }
+        let now = Instant::now();
+        match self.frames.last() {
+            Some((_, last)) if now - *last < self.min_interval => {
+                self.pending = Some(*display);
+            }
+            _ => {
+                self.frames.push((*display, now));
+                self.pending = None;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::test_vm;
+
+    fn decode(gif: &[u8]) -> (u16, u16, usize) {
+        let mut decoder = ::gif::Decoder::new(gif).unwrap();
+        let (width, height) = (decoder.width(), decoder.height());
+        let mut frames = 0;
+        while decoder.read_next_frame().unwrap().is_some() {
+            frames += 1;
+        }
+        (width, height, frames)
+    }
+
+    #[test]
+    fn record_ibm() {
+        let mut vm = test_vm();
+        vm.load_rom_from_file("ibm.ch8");
+        let mut recorder = GifRecorder::new(4).with_max_fps(0);
+        for _ in 0..3 {
+            for _ in 0..10 {
+                vm.run_once().unwrap();
+            }
+            recorder.render(&vm.display);
+        }
+
+        let gif = recorder.finish(Vec::new()).unwrap();
+        assert_eq!(decode(&gif), (64 * 4, 32 * 4, 3));
+    }
+
+    #[test]
+    fn rate_cap_flushes_last_frame() {
+        let mut recorder = GifRecorder::new(1).with_max_fps(1);
+        let mut display = [[false; 64]; 32];
+        recorder.render(&display);
+        for x in 0..5 {
+            display[0][x] = true;
+            recorder.render(&display);
+        }
+        assert_eq!(recorder.frame_count(), 2);
+        let gif = recorder.finish(Vec::new()).unwrap();
+        assert_eq!(decode(&gif), (64, 32, 2));
+    }
+
+    #[test]
+    fn frame_limit() {
+        let mut recorder = GifRecorder::new(1).with_max_fps(0).with_max_frames(4);
+        for _ in 0..10 {
+            recorder.render(&[[true; 64]; 32]);
+        }
+        assert_eq!(recorder.frame_count(), 4);
+    }
+}
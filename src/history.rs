@@ -0,0 +1,176 @@
+use alloc::{boxed::Box, collections::VecDeque, vec::Vec};
+
+use crate::{Chip8Instr, Chip8VM, Display, Registers, Timers};
+
+pub(crate) type History = VecDeque<Snapshot>;
+
+// What an instruction may change, recorded before it runs.
+// Only the RAM bytes and display the instruction can write are kept,
+// instead of a full 4kB + 2kB copy per step.
+pub(crate) struct Snapshot {
+    registers: Registers,
+    stack: Vec<u16>,
+    timers: Timers,
+    key_wait: Option<u8>,
+    cycles: u64,
+    // Start address and previous content of the RAM written by the instruction
+    ram: Option<(usize, Vec<u8>)>,
+    display: Option<Box<Display>>,
+    // Second XO-CHIP plane, when selected for the instruction
+    plane2: Option<Box<Display>>,
+    planes: u8,
+    display_changed: bool,
+}
+
+impl Chip8VM {
+    pub(crate) fn record_history(&mut self, instruction: &Chip8Instr) {
+        let i = self.registers.i as usize;
+        let written = match *instruction {
+            Chip8Instr::Save(x) => Some(i..i + x as usize + 1),
+            Chip8Instr::Decimal(_) => Some(i..i + 3),
+            _ => None,
+        };
+        let ram = written.map(|range| {
+            let end = range.end.min(Self::RAM_SIZE);
+            let start = range.start.min(end);
+            (start, self.ram[start..end].to_vec())
+        });
+        let draws = matches!(instruction, Chip8Instr::Clear | Chip8Instr::Display(..));
+        let display = draws.then(|| Box::new(self.display));
+        let plane2 = (draws && self.planes & 2 != 0).then(|| Box::new(self.plane2));
+
+        if self.history.len() >= self.options.history_depth {
+            self.history.pop_front();
+        }
+        self.history.push_back(Snapshot {
+            registers: self.registers,
+            stack: self.stack.clone(),
+            timers: self.timers.get(),
+            key_wait: self.key_wait,
+            cycles: self.cycles,
+            ram,
+            display,
+            plane2,
+            planes: self.planes,
+            display_changed: self.display_changed,
+        });
+    }
+
+    // Undoes the last instruction, up to `history_depth` times.
+    // Returns false when there is nothing left to undo.
+    // The keypad and the random number generator are not rewound.
+    pub fn step_back(&mut self) -> bool {
+        let Some(snapshot) = self.history.pop_back() else {
+            return false;
+        };
+        self.registers = snapshot.registers;
+        self.stack = snapshot.stack;
+        self.timers.modify(|t| *t = snapshot.timers);
+        self.key_wait = snapshot.key_wait;
+        // Set again if the jump runs again
+        self.halted = false;
+        self.cycles = snapshot.cycles;
+        if let Some((start, bytes)) = snapshot.ram {
+            self.ram[start..start + bytes.len()].copy_from_slice(&bytes);
+            self.ram_written(start, start + bytes.len());
+        }
+        self.planes = snapshot.planes;
+        if let Some(plane2) = snapshot.plane2 {
+            self.plane2 = *plane2;
+        }
+        if let Some(display) = snapshot.display {
+            self.display = *display;
+            self.display_changed = true;
+        } else {
+            self.display_changed |= snapshot.display_changed;
+        }
+        true
+    }
+
+    // Number of instructions that can currently be undone
+    pub fn history_len(&self) -> usize {
+        self.history.len()
+    }
+
+    pub fn clear_history(&mut self) {
+        self.history.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tests::test_vm;
+
+    use super::*;
+
+    fn vm_with_history(depth: usize) -> Chip8VM {
+        let mut vm = test_vm();
+        vm.options.history_depth = depth;
+        vm
+    }
+
+    #[test]
+    fn step_back_restores() {
+        let mut vm = vm_with_history(16);
+        vm.load_rom(&[
+            0x60, 0x7B, // LD V0, 123
+            0xA3, 0x00, // LD I, 0x300
+            0xF0, 0x33, // LD B, V0
+            0xF0, 0x29, // LD F, V0
+            0xD1, 0x15, // DRW V1, V1, 5
+            0x22, 0x00, // CALL 0x200
+        ]);
+        let mut states = Vec::new();
+        for _ in 0..6 {
+            states.push(vm.to_state());
+            vm.run_once().unwrap();
+        }
+        assert_eq!(vm.history_len(), 6);
+        assert_eq!(vm.read_mem_slice(0x300, 3), Some(&[1, 2, 3][..]));
+
+        for state in states.iter().rev() {
+            assert!(vm.step_back());
+            assert_eq!(&vm.to_state(), state);
+        }
+        assert!(!vm.step_back());
+        assert_eq!(vm.cycles(), 0);
+    }
+
+    #[test]
+    fn history_depth_limit() {
+        let mut vm = vm_with_history(3);
+        // ADD V0, 1 ; JP 0x200
+        vm.load_rom(&[0x70, 0x01, 0x12, 0x00]);
+        for _ in 0..10 {
+            vm.run_once().unwrap();
+        }
+        assert_eq!(vm.history_len(), 3);
+        while vm.step_back() {}
+        assert_eq!(vm.registers.get(0), 4);
+
+        let mut vm = vm_with_history(0);
+        vm.run_once().unwrap();
+        assert_eq!(vm.history_len(), 0);
+    }
+
+    #[test]
+    fn step_back_planes() {
+        let mut vm = vm_with_history(4);
+        // LD I, 0x300 ; DRW V0, V0, 1 ; CLS
+        vm.load_rom(&[0xA3, 0x00, 0xD0, 0x01, 0x00, 0xE0]);
+        vm.ram[0x300..0x302].copy_from_slice(&[0x80, 0x40]);
+        vm.select_planes(0b11);
+        vm.plane2[5][5] = true;
+        vm.step(2);
+        assert!(vm.display[0][0] && vm.plane2[0][1]);
+        vm.run_once().unwrap();
+        assert!(!vm.plane2[5][5]);
+
+        vm.select_planes(0b01);
+        assert!(vm.step_back());
+        assert_eq!(vm.selected_planes(), 0b11);
+        assert!(vm.plane2[0][1] && vm.plane2[5][5]);
+        assert!(vm.step_back());
+        assert!(!vm.display[0][0] && !vm.plane2[0][1] && vm.plane2[5][5]);
+    }
+}
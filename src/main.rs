@@ -1,30 +1,215 @@
 use chip_8::*;
-fn main() -> std::io::Result<()> {
-    // let mut vm = Chip8VM::new(
-    //     Some(10),
-    //     None,
-    //     Some(Chip8VMOptions {
-    //         debug: true,
-    //         // hide_display: trcue,
-    //         keep_display: true,
-    //         new_jump_off: true,
-    //         ..Default::default()
-    //     }),
-    // );
-    let mut vm = Chip8VM::new(
-        Some(12),
-        None,
-        Some(Chip8VMOptions {
-            keep_display: true,
-            hide_display: true,
-            ..Default::default()
-        }),
-    );
-
-    vm.load_rom_from_file("ibm.ch8");
-    println!("{:?}", vm);
-
-    vm.run();
-
-    Ok(())
+use std::process::ExitCode;
+
+const USAGE: &str = "usage: chip-8 <rom.ch8> [options]
+       chip-8 disasm <rom.ch8> [--out <file.asm>]
+    --freq <hz>        instructions per second, from 1 to 10000000 (default 700)
+    --cycles <n>       run n instructions per 60Hz frame instead of following --freq
+    --debug            print each executed instruction
+    --trace <file>     write the state before each instruction to a file
+    --bench            run the ROM as fast as possible for 5 seconds and print the speed
+    --stats            print the executed instructions and hottest addresses at the end
+    --profile <name>   quirks of an interpreter: vip, chip48, schip or xochip
+    --schip            same as --profile schip
+    --byte-swap        swap the bytes of each instruction of a little-endian ROM dump
+    --keep-display     print frames one after the other
+    --border           draw a frame around the display
+    --status           print PC and the cycle count above the display
+    --hide-display     don't draw the display
+    --no-keypad        don't read the keypad from the terminal
+    --out <file.asm>   disasm: write the listing to a file instead of stdout";
+
+struct Args {
+    rom: String,
+    freq: Option<u32>,
+    trace: Option<String>,
+    bench: bool,
+    stats: bool,
+    options: Chip8VMOptions,
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Args, String> {
+    let mut rom = None;
+    let mut freq = None;
+    let mut trace = None;
+    let mut bench = false;
+    let mut stats = false;
+    let mut options = Chip8VMOptions {
+        terminal_keypad: true,
+        // Suggests a profile for SUPER-CHIP and XO-CHIP ROMs, unless one is chosen
+        detect_extensions: true,
+        ..Default::default()
+    };
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--freq" => {
+                let value = args.next().ok_or("--freq needs a value")?;
+                freq = Some(
+                    value
+                        .parse()
+                        .map_err(|_| format!("invalid frequency '{value}'"))?,
+                );
+            }
+            "--cycles" => {
+                let value = args.next().ok_or("--cycles needs a value")?;
+                let cycles = value
+                    .parse()
+                    .map_err(|_| format!("invalid number of cycles '{value}'"))?;
+                options.timing = TimingModel::CyclesPerFrame(cycles);
+            }
+            "--bench" => bench = true,
+            "--stats" => {
+                stats = true;
+                options.opcode_stats = true;
+            }
+            "--debug" => options.debug = true,
+            "--trace" => trace = Some(args.next().ok_or("--trace needs a file")?),
+            "--profile" => {
+                let value = args.next().ok_or("--profile needs a value")?;
+                let profile = match value.as_str() {
+                    "vip" => Profile::CosmacVip,
+                    "chip48" => Profile::Chip48,
+                    "schip" => Profile::SuperChip,
+                    "xochip" => Profile::XoChip,
+                    _ => return Err(format!("unknown profile '{value}'")),
+                };
+                set_quirks(&mut options, profile);
+            }
+            "--schip" => set_quirks(&mut options, Profile::SuperChip),
+            "--byte-swap" => options.byte_swap = true,
+            "--keep-display" => options.keep_display = true,
+            "--border" => options.terminal_border = true,
+            "--status" => options.terminal_status = true,
+            "--hide-display" => options.hide_display = true,
+            "--no-keypad" => options.terminal_keypad = false,
+            "-h" | "--help" => return Err(USAGE.to_string()),
+            flag if flag.starts_with('-') => return Err(format!("unknown option '{flag}'")),
+            _ if rom.is_some() => return Err(format!("unexpected argument '{arg}'")),
+            _ => rom = Some(arg),
+        }
+    }
+    Ok(Args {
+        rom: rom.ok_or(USAGE)?,
+        freq,
+        trace,
+        bench,
+        stats,
+        options,
+    })
+}
+
+// Only the quirks, so the other flags can come in any order. No profile is suggested
+// once one is chosen.
+fn set_quirks(options: &mut Chip8VMOptions, profile: Profile) {
+    options.detect_extensions = false;
+    let quirks = profile.options();
+    options.old_shift = quirks.old_shift;
+    options.new_jump_off = quirks.new_jump_off;
+    options.incr_i_when_mem = quirks.incr_i_when_mem;
+    options.incr_i_past_last = quirks.incr_i_past_last;
+    options.vf_reset = quirks.vf_reset;
+    options.display_wait = quirks.display_wait;
+    options.wrap_sprites = quirks.wrap_sprites;
+}
+
+// `chip-8 disasm`: prints the annotated listing of a ROM, see `disassemble_annotated`
+fn disasm(mut args: impl Iterator<Item = String>) -> Result<(), String> {
+    let mut rom = None;
+    let mut out = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--out" => out = Some(args.next().ok_or("--out needs a file")?),
+            "-h" | "--help" => return Err(USAGE.to_string()),
+            flag if flag.starts_with('-') => return Err(format!("unknown option '{flag}'")),
+            _ if rom.is_some() => return Err(format!("unexpected argument '{arg}'")),
+            _ => rom = Some(arg),
+        }
+    }
+    let rom: String = rom.ok_or(USAGE)?;
+    let bytes = std::fs::read(&rom).map_err(|e| format!("cannot read ROM '{rom}': {e}"))?;
+    let listing = disassembler::disassemble_annotated(&bytes);
+    match out {
+        Some(out) => {
+            std::fs::write(&out, listing).map_err(|e| format!("cannot write '{out}': {e}"))
+        }
+        None => {
+            print!("{listing}");
+            Ok(())
+        }
+    }
+}
+
+fn main() -> ExitCode {
+    let mut args = std::env::args().skip(1).peekable();
+    if args.peek().map(String::as_str) == Some("disasm") {
+        args.next();
+        return match disasm(args) {
+            Ok(()) => ExitCode::SUCCESS,
+            Err(e) => {
+                eprintln!("{e}");
+                ExitCode::FAILURE
+            }
+        };
+    }
+    let args = match parse_args(args) {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("{e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let rom = match std::fs::read(&args.rom) {
+        Ok(rom) => rom,
+        Err(e) => {
+            eprintln!("cannot read ROM '{}': {e}", args.rom);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let mut vm = Chip8VM::new(None, None, Some(args.options));
+    if let Some(freq) = args.freq {
+        if let Err(e) = vm.set_freq(freq) {
+            eprintln!("{e}");
+            return ExitCode::FAILURE;
+        }
+    }
+    if let Some(trace) = &args.trace {
+        if let Err(e) = vm.trace_to_file(trace) {
+            eprintln!("cannot create trace file '{trace}': {e}");
+            return ExitCode::FAILURE;
+        }
+    }
+    match vm.try_load_rom(&rom) {
+        Ok(Some(warning)) => eprintln!("warning: {warning}"),
+        Ok(None) => {}
+        Err(e) => {
+            eprintln!("cannot load ROM '{}': {e}", args.rom);
+            return ExitCode::FAILURE;
+        }
+    }
+    if args.bench {
+        return match vm.benchmark(std::time::Duration::from_secs(5)) {
+            Ok(result) => {
+                println!(
+                    "{} instructions in {:.2?}: {:.0} Hz, {} frames",
+                    result.instructions, result.elapsed, result.hz, result.frames
+                );
+                ExitCode::SUCCESS
+            }
+            Err(e) => {
+                eprintln!("{e}");
+                ExitCode::FAILURE
+            }
+        };
+    }
+    let result = vm.run();
+    if args.stats {
+        print!("{}", vm.run_stats(10));
+    }
+    if let Err(e) = result {
+        eprintln!("{e}");
+        return ExitCode::FAILURE;
+    }
+
+    ExitCode::SUCCESS
 }
@@ -0,0 +1,473 @@
+use alloc::collections::BTreeMap;
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+
+use crate::assembler::AsmError;
+use crate::Chip8VM;
+
+// Octo words of the full language that this importer doesn't handle
+const UNSUPPORTED: &[&str] = &[
+    ":macro",
+    ":calc",
+    ":alias",
+    ":org",
+    ":next",
+    ":unpack",
+    ":breakpoint",
+    ":monitor",
+    ":assert",
+    ":byte",
+    ":pointer",
+    ":stringmode",
+    "while",
+    "begin",
+    "else",
+    "end",
+    "hires",
+    "lores",
+    "scroll-up",
+    "scroll-down",
+    "scroll-left",
+    "scroll-right",
+    "exit",
+    "plane",
+    "audio",
+    "pitch",
+    "saveflags",
+    "loadflags",
+    "long",
+];
+
+struct Token<'a> {
+    line: usize,
+    text: &'a str,
+}
+
+// Assembles a subset of the Octo language into a ROM loaded at 0x200:
+// - `: name` labels, `:const name value`, a bare label name calls it
+// - `clear`, `return` (or `;`), `jump`, `jump0`, `loop` ... `again`
+// - `vx := n|vy|random n|delay|key`, `vx += n|vy`, `vx -= vy`, `vx =- vy`,
+//   `|=`, `&=`, `^=`, `>>=`, `<<=`
+// - `i := n|label|hex vx|bighex vx`, `i += vx`, `delay := vx`, `buzzer := vx`
+// - `if vx == n|vy then`, `if vx != n|vy then`, `if vx key then`, `if vx -key then`
+// - `sprite vx vy n`, `bcd vx`, `save vx`, `load vx`, numbers emit bytes
+//
+// Like Octo, execution starts at `main`: a jump to it is emitted at 0x200 unless it is
+// the first label. Other Octo constructs are reported as unsupported.
+pub fn assemble_octo(source: &str) -> Result<Vec<u8>, AsmError> {
+    let tokens: Vec<Token> = source
+        .lines()
+        .enumerate()
+        .flat_map(|(idx, raw)| {
+            let text = raw.split('#').next().unwrap_or("");
+            text.split_whitespace().map(move |text| Token {
+                line: idx + 1,
+                text,
+            })
+        })
+        .collect();
+    let mut parser = Parser {
+        tokens: tokens.iter(),
+        line: 1,
+        rom: Vec::new(),
+        labels: BTreeMap::new(),
+        consts: BTreeMap::new(),
+        fixups: Vec::new(),
+        loops: Vec::new(),
+    };
+
+    let main_first =
+        matches!(&tokens[..], [colon, name, ..] if colon.text == ":" && name.text == "main");
+    let has_main = tokens
+        .windows(2)
+        .any(|w| w[0].text == ":" && w[1].text == "main");
+    if has_main && !main_first {
+        parser.emit_addr(0x1000, "main");
+    }
+    while let Some(token) = parser.tokens.next() {
+        parser.line = token.line;
+        parser.statement(token.text)?;
+    }
+    if let Some(&(_, line)) = parser.loops.last() {
+        return Err(AsmError {
+            line,
+            message: "'loop' without 'again'".to_string(),
+        });
+    }
+
+    if parser.rom.len() > Chip8VM::RAM_SIZE - Chip8VM::RAM_ROM_START {
+        return Err(parser.error("program does not fit in RAM".to_string()));
+    }
+
+    for (offset, name, line) in &parser.fixups {
+        let addr = parser.labels.get(name).ok_or_else(|| AsmError {
+            line: *line,
+            message: format!("unknown label '{name}'"),
+        })?;
+        parser.rom[*offset] |= (addr >> 8) as u8;
+        parser.rom[*offset + 1] = *addr as u8;
+    }
+    Ok(parser.rom)
+}
+
+struct Parser<'a, 't> {
+    tokens: core::slice::Iter<'t, Token<'a>>,
+    line: usize,
+    rom: Vec<u8>,
+    labels: BTreeMap<&'a str, u16>,
+    consts: BTreeMap<&'a str, u16>,
+    // Offset of an instruction whose address is the label's, with the line using it
+    fixups: Vec<(usize, &'a str, usize)>,
+    // Start address and line of the open `loop`s
+    loops: Vec<(u16, usize)>,
+}
+
+impl<'a> Parser<'a, '_> {
+    fn statement(&mut self, word: &'a str) -> Result<(), AsmError> {
+        match word {
+            ":" => {
+                let name = self.identifier()?;
+                let addr = self.addr();
+                if self.labels.insert(name, addr).is_some() {
+                    return Err(self.error(format!("duplicate label '{name}'")));
+                }
+            }
+            ":const" => {
+                let name = self.identifier()?;
+                let value = self.value(0xFFF)?;
+                self.consts.insert(name, value);
+            }
+            "clear" => self.emit(0x00E0),
+            "return" | ";" => self.emit(0x00EE),
+            "jump" => {
+                let target = self.identifier()?;
+                self.emit_addr(0x1000, target);
+            }
+            "jump0" => {
+                let target = self.identifier()?;
+                self.emit_addr(0xB000, target);
+            }
+            "loop" => self.loops.push((self.addr(), self.line)),
+            "again" => {
+                let (start, _) = self
+                    .loops
+                    .pop()
+                    .ok_or_else(|| self.error("'again' without 'loop'".to_string()))?;
+                self.emit(0x1000 | start);
+            }
+            "sprite" => {
+                let x = self.register()?;
+                let y = self.register()?;
+                let n = self.value(0xF)?;
+                self.emit(xy(0xD000, x, y) | n);
+            }
+            "bcd" => self.emit_x(0xF033)?,
+            "save" => self.emit_x(0xF055)?,
+            "load" => self.emit_x(0xF065)?,
+            "if" => self.condition()?,
+            "i" => self.assign_i()?,
+            "delay" | "buzzer" => {
+                self.expect(":=")?;
+                let x = self.register()?;
+                let low = if word == "delay" { 0x15 } else { 0x18 };
+                self.emit(xnn(0xF000, x, low));
+            }
+            w if parse_register(w).is_some() => self.assign_register(w)?,
+            w if UNSUPPORTED.contains(&w) || w.starts_with(':') => {
+                return Err(self.error(format!("unsupported Octo directive '{w}'")));
+            }
+            w if w.starts_with(|c: char| c.is_ascii_digit() || c == '-') => {
+                let byte = self.number(w, 0xFF)?;
+                self.rom.push(byte as u8);
+            }
+            w if is_identifier(w) => self.emit_addr(0x2000, w),
+            w => return Err(self.error(format!("unexpected '{w}'"))),
+        }
+        Ok(())
+    }
+
+    fn assign_register(&mut self, target: &str) -> Result<(), AsmError> {
+        let x = parse_register(target).unwrap_or_default();
+        let op = self.next()?;
+        let rhs = self.next()?;
+        let opcode = match (op, rhs, parse_register(rhs)) {
+            (":=", _, Some(y)) => xy(0x8000, x, y),
+            (":=", "random", _) => {
+                let mask = self.value(0xFF)?;
+                xnn(0xC000, x, mask as u8)
+            }
+            (":=", "delay", _) => xnn(0xF000, x, 0x07),
+            (":=", "key", _) => xnn(0xF000, x, 0x0A),
+            (":=", value, _) => xnn(0x6000, x, self.byte(value)?),
+            ("+=", _, Some(y)) => xy(0x8004, x, y),
+            ("+=", value, _) => xnn(0x7000, x, self.byte(value)?),
+            ("|=", _, Some(y)) => xy(0x8001, x, y),
+            ("&=", _, Some(y)) => xy(0x8002, x, y),
+            ("^=", _, Some(y)) => xy(0x8003, x, y),
+            ("-=", _, Some(y)) => xy(0x8005, x, y),
+            (">>=", _, Some(y)) => xy(0x8006, x, y),
+            ("=-", _, Some(y)) => xy(0x8007, x, y),
+            ("<<=", _, Some(y)) => xy(0x800E, x, y),
+            _ => return Err(self.error(format!("invalid operation '{target} {op} {rhs}'"))),
+        };
+        self.emit(opcode);
+        Ok(())
+    }
+
+    fn assign_i(&mut self) -> Result<(), AsmError> {
+        match self.next()? {
+            ":=" => match self.next()? {
+                "hex" => self.emit_x(0xF029)?,
+                "bighex" => self.emit_x(0xF030)?,
+                w if self.consts.contains_key(w) || w.starts_with(|c: char| c.is_ascii_digit()) => {
+                    let addr = self.number(w, 0xFFF)?;
+                    self.emit(0xA000 | addr);
+                }
+                w if is_identifier(w) => self.emit_addr(0xA000, w),
+                w => return Err(self.error(format!("invalid address '{w}'"))),
+            },
+            "+=" => self.emit_x(0xF01E)?,
+            op => return Err(self.error(format!("invalid operation 'i {op}'"))),
+        }
+        Ok(())
+    }
+
+    // `if cond then` skips the next instruction when the condition is false
+    fn condition(&mut self) -> Result<(), AsmError> {
+        let x = self.register()?;
+        let op = self.next()?;
+        let opcode = match op {
+            "key" => xnn(0xE000, x, 0xA1),
+            "-key" => xnn(0xE000, x, 0x9E),
+            "==" | "!=" => {
+                let rhs = self.next()?;
+                match (op, parse_register(rhs)) {
+                    ("==", Some(y)) => xy(0x9000, x, y),
+                    ("!=", Some(y)) => xy(0x5000, x, y),
+                    ("==", None) => xnn(0x4000, x, self.byte(rhs)?),
+                    _ => xnn(0x3000, x, self.byte(rhs)?),
+                }
+            }
+            _ => return Err(self.error(format!("unsupported condition '{op}'"))),
+        };
+        self.expect("then")?;
+        self.emit(opcode);
+        Ok(())
+    }
+
+    fn next(&mut self) -> Result<&'a str, AsmError> {
+        let token = self
+            .tokens
+            .next()
+            .ok_or_else(|| self.error("unexpected end of file".to_string()))?;
+        self.line = token.line;
+        Ok(token.text)
+    }
+
+    fn expect(&mut self, word: &str) -> Result<(), AsmError> {
+        match self.next()? {
+            w if w == word => Ok(()),
+            w => Err(self.error(format!("expected '{word}', got '{w}'"))),
+        }
+    }
+
+    fn identifier(&mut self) -> Result<&'a str, AsmError> {
+        match self.next()? {
+            w if is_identifier(w) => Ok(w),
+            w => Err(self.error(format!("invalid name '{w}'"))),
+        }
+    }
+
+    fn register(&mut self) -> Result<u8, AsmError> {
+        let w = self.next()?;
+        parse_register(w).ok_or_else(|| self.error(format!("expected a register, got '{w}'")))
+    }
+
+    fn value(&mut self, max: u16) -> Result<u16, AsmError> {
+        let w = self.next()?;
+        self.number(w, max)
+    }
+
+    fn byte(&self, w: &str) -> Result<u8, AsmError> {
+        Ok(self.number(w, 0xFF)? as u8)
+    }
+
+    // Literal or constant, negative numbers are two's complement bytes
+    fn number(&self, w: &str, max: u16) -> Result<u16, AsmError> {
+        if let Some(&value) = self.consts.get(w) {
+            return self.check(value, max);
+        }
+        let (negative, digits) = match w.strip_prefix('-') {
+            Some(digits) if max == 0xFF => (true, digits),
+            _ => (false, w),
+        };
+        let parsed = if let Some(hex) = digits.strip_prefix("0x") {
+            u16::from_str_radix(hex, 16)
+        } else if let Some(bin) = digits.strip_prefix("0b") {
+            u16::from_str_radix(bin, 2)
+        } else {
+            digits.parse()
+        };
+        let value = parsed.map_err(|_| self.error(format!("invalid number '{w}'")))?;
+        if negative {
+            if value > 0x80 {
+                return Err(self.error(format!("value {w} smaller than -128")));
+            }
+            return Ok((value as u8).wrapping_neg() as u16);
+        }
+        self.check(value, max)
+    }
+
+    fn check(&self, value: u16, max: u16) -> Result<u16, AsmError> {
+        if value > max {
+            return Err(self.error(format!("value {value:#x} larger than {max:#x}")));
+        }
+        Ok(value)
+    }
+
+    fn addr(&self) -> u16 {
+        (Chip8VM::RAM_ROM_START + self.rom.len()) as u16
+    }
+
+    fn emit(&mut self, opcode: u16) {
+        self.rom.extend_from_slice(&opcode.to_be_bytes());
+    }
+
+    fn emit_x(&mut self, base: u16) -> Result<(), AsmError> {
+        let x = self.register()?;
+        self.emit(base | (x as u16) << 8);
+        Ok(())
+    }
+
+    // Instruction taking the address of a label, resolved once every label is known
+    fn emit_addr(&mut self, base: u16, label: &'a str) {
+        self.fixups.push((self.rom.len(), label, self.line));
+        self.emit(base);
+    }
+
+    fn error(&self, message: String) -> AsmError {
+        AsmError {
+            line: self.line,
+            message,
+        }
+    }
+}
+
+fn parse_register(w: &str) -> Option<u8> {
+    let digit = w.strip_prefix(['v', 'V'])?;
+    if digit.len() != 1 {
+        return None;
+    }
+    u8::from_str_radix(digit, 16).ok()
+}
+
+fn is_identifier(s: &str) -> bool {
+    s.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && s.chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+fn xy(base: u16, x: u8, y: u8) -> u16 {
+    base | (x as u16) << 8 | (y as u16) << 4
+}
+
+fn xnn(base: u16, x: u8, nn: u8) -> u16 {
+    base | (x as u16) << 8 | nn as u16
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assembler::assemble;
+
+    #[test]
+    fn same_as_assembler() {
+        let octo = "
+            :const speed 3
+            : draw
+                i := smiley
+                sprite v0 v1 4
+            ;
+
+            : main     # entry point
+                clear
+                v0 := 10
+                v1 := v0
+                v1 += speed
+                v2 := random 0xFF
+                loop
+                    draw
+                    v3 := key
+                    if v3 == 5 then v0 += 1
+                    if v3 != v4 then v0 -= v4
+                    if v3 key then v1 >>= v1
+                    i := hex v3
+                    bcd v3
+                    delay := v3
+                    v5 := delay
+                    i += v5
+                    save v5
+                again
+            : smiley
+                0b00100100 0 0x42 -1
+        ";
+        let asm = "
+                JP main
+            draw:
+                LD I, smiley
+                DRW V0, V1, 4
+                RET
+            main:
+                CLS
+                LD V0, 10
+                LD V1, V0
+                ADD V1, 3
+                RND V2, 0xFF
+            loop:
+                CALL draw
+                LD V3, K
+                SNE V3, 5
+                ADD V0, 1
+                SE V3, V4
+                SUB V0, V4
+                SKNP V3
+                SHR V1, V1
+                LD F, V3
+                LD B, V3
+                LD DT, V3
+                LD V5, DT
+                ADD I, V5
+                LD [I], V5
+                JP loop
+            smiley:
+                DB 0b00100100, 0, 0x42, 0xFF
+        ";
+        assert_eq!(assemble_octo(octo).unwrap(), assemble(asm).unwrap());
+    }
+
+    #[test]
+    fn main_first() {
+        let rom = assemble_octo(": main\n v0 := 1\n jump main").unwrap();
+        assert_eq!(rom, [0x60, 0x01, 0x12, 0x00]);
+        // Without main, starts at the top
+        assert_eq!(assemble_octo("clear").unwrap(), [0x00, 0xE0]);
+    }
+
+    #[test]
+    fn octo_errors() {
+        let err = assemble_octo(": main\n  while v0 == 1 begin").unwrap_err();
+        assert_eq!(err.line, 2);
+        assert!(err.message.contains("unsupported Octo directive 'while'"));
+        assert_eq!(
+            assemble_octo(": main\n\n jump nowhere").unwrap_err().line,
+            3
+        );
+        assert_eq!(assemble_octo("v0 := 256").unwrap_err().line, 1);
+        assert_eq!(assemble_octo("loop\n clear").unwrap_err().line, 1);
+        assert_eq!(assemble_octo(": a\n: a").unwrap_err().line, 2);
+        assert_eq!(assemble_octo("\nv0 +=").unwrap_err().line, 2);
+    }
+}
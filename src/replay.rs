@@ -0,0 +1,261 @@
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+
+use rand::{rngs::StdRng, SeedableRng};
+
+use crate::{Chip8Error, Chip8VM, TimersHandle};
+
+// Replay files are text, one entry per line:
+//
+//   chip8-replay 1
+//   seed 1234
+//   0 K 0000
+//   11 T
+//   11 K 0020
+//   ...
+//   4000 END
+//
+// after the header and the rng seed, each line starts with the number of instructions
+// executed since the start of the recording:
+// - `K <hex>`: the keypad (bit n for key n) changed before the next instruction
+// - `T`: a 60Hz frame with a timers tick, `F` for a frame while paused (no tick)
+// - `END`: where the recording stopped
+const MAGIC: &str = "chip8-replay";
+const VERSION: u32 = 1;
+
+pub(crate) struct Recorder {
+    out: BufWriter<File>,
+    start: u64,
+    // Last keypad written, only the changes are
+    keys: Option<u16>,
+    // Including the instruction being run, which replays its error if it failed
+    end: u64,
+}
+impl Drop for Recorder {
+    fn drop(&mut self) {
+        let _ = writeln!(self.out, "{} END", self.end);
+        let _ = self.out.flush();
+    }
+}
+
+#[derive(Debug)]
+pub enum ReplayError {
+    Io(io::Error),
+    // Not a replay file, or an unsupported version
+    Header,
+    // Malformed entry, at this line (1 based)
+    Line(usize),
+    // The VM failed while replaying, like in the recorded session
+    Vm(Chip8Error),
+}
+impl core::fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "{e}"),
+            Self::Header => write!(f, "not a version {VERSION} replay file"),
+            Self::Line(line) => write!(f, "invalid replay entry at line {line}"),
+            Self::Vm(e) => write!(f, "{e}"),
+        }
+    }
+}
+impl std::error::Error for ReplayError {}
+impl From<io::Error> for ReplayError {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+impl From<Chip8Error> for ReplayError {
+    fn from(e: Chip8Error) -> Self {
+        Self::Vm(e)
+    }
+}
+
+enum Entry {
+    Keys(u16),
+    Frame { tick: bool },
+    End,
+}
+
+impl Chip8VM {
+    // Records the keypad and frames of this session to `path` until `stop_recording`,
+    // so `replay_from` can play it again exactly. Start it right after loading the ROM.
+    // The rng is replaced by a seeded one (the seed is saved) and the timers switch to
+    // manual mode, ticked by the frames of `run`, so that both are reproducible.
+    pub fn record_to(&mut self, path: impl AsRef<Path>) -> io::Result<()> {
+        let seed = rand::random();
+        let mut out = BufWriter::new(File::create(path)?);
+        writeln!(out, "{MAGIC} {VERSION}")?;
+        writeln!(out, "seed {seed}")?;
+        self.set_rng(Box::new(StdRng::seed_from_u64(seed)));
+        self.use_manual_timers();
+        self.recorder = Some(Recorder {
+            out,
+            start: self.cycles,
+            keys: None,
+            end: 0,
+        });
+        Ok(())
+    }
+
+    // Ends the recording file, also done when the VM is dropped
+    pub fn stop_recording(&mut self) {
+        self.recorder = None;
+    }
+
+    // Plays the recording at `path` on this VM, which must have just loaded the same ROM
+    // with the same options, until the end of the recording or the first error of the VM.
+    // Runs as fast as possible, presenting the frames where the display changed.
+    pub fn replay_from(&mut self, path: impl AsRef<Path>) -> Result<(), ReplayError> {
+        let mut lines = BufReader::new(File::open(path)?).lines();
+        let header = lines.next().transpose()?.ok_or(ReplayError::Header)?;
+        if header != format!("{MAGIC} {VERSION}") {
+            return Err(ReplayError::Header);
+        }
+        let seed = lines.next().transpose()?;
+        let seed = seed
+            .as_deref()
+            .and_then(|line| line.strip_prefix("seed "))
+            .and_then(|seed| seed.parse().ok())
+            .ok_or(ReplayError::Line(2))?;
+        let mut entries = Vec::new();
+        for (n, line) in lines.enumerate() {
+            let line = line?;
+            entries.push(parse_entry(&line).ok_or(ReplayError::Line(n + 3))?);
+        }
+
+        self.set_rng(Box::new(StdRng::seed_from_u64(seed)));
+        self.use_manual_timers();
+        let start = self.cycles;
+        for (cycle, entry) in entries {
+            while self.cycles - start < cycle {
+                self.run_once()?;
+            }
+            match entry {
+                Entry::Keys(keys) => self.set_keypad_state(keys),
+                Entry::Frame { tick } => {
+                    let paused = self.paused;
+                    self.paused = !tick;
+                    self.tick_timers();
+                    self.paused = paused;
+                    if self.display_changed || self.fading {
+                        self.present_frame();
+                    }
+                }
+                Entry::End => break,
+            }
+        }
+        Ok(())
+    }
+
+    // Before each instruction
+    pub(crate) fn record_keys(&mut self) {
+        let keys = self.keypad.bits();
+        let cycles = self.cycles;
+        let Some(recorder) = &mut self.recorder else {
+            return;
+        };
+        let cycle = cycles - recorder.start;
+        recorder.end = cycle + 1;
+        if recorder.keys != Some(keys) {
+            recorder.keys = Some(keys);
+            let _ = writeln!(recorder.out, "{cycle} K {keys:04x}");
+        }
+    }
+
+    // At each frame, before the timers tick
+    pub(crate) fn record_frame(&mut self) {
+        let tick = if self.paused { 'F' } else { 'T' };
+        let cycles = self.cycles;
+        if let Some(recorder) = &mut self.recorder {
+            let cycle = cycles - recorder.start;
+            recorder.end = recorder.end.max(cycle);
+            let _ = writeln!(recorder.out, "{cycle} {tick}");
+        }
+    }
+
+    fn use_manual_timers(&mut self) {
+        if !matches!(self.timers, TimersHandle::Manual(_)) {
+            self.timers = TimersHandle::Manual(self.timers.get());
+        }
+    }
+}
+
+fn parse_entry(line: &str) -> Option<(u64, Entry)> {
+    let mut words = line.split_whitespace();
+    let cycle = words.next()?.parse().ok()?;
+    let entry = match (words.next()?, words.next()) {
+        ("K", Some(keys)) => Entry::Keys(u16::from_str_radix(keys, 16).ok()?),
+        ("T", None) => Entry::Frame { tick: true },
+        ("F", None) => Entry::Frame { tick: false },
+        ("END", None) => Entry::End,
+        _ => return None,
+    };
+    words.next().is_none().then_some((cycle, entry))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::test_vm;
+
+    fn vm() -> Chip8VM {
+        let mut vm = test_vm();
+        vm.load_rom(include_bytes!("../KALEID.ch8"));
+        vm
+    }
+
+    #[test]
+    fn record_and_replay() {
+        let path = std::env::temp_dir().join(format!("chip-8-replay-{}.txt", std::process::id()));
+        let mut recorded = vm();
+        recorded.record_to(&path).unwrap();
+        // KALEID saves the keys in its RAM, the rng is checked with a Rand at the end
+        for cycle in 0..3000 {
+            if cycle % 12 == 0 {
+                recorded.tick_timers();
+            }
+            if cycle == 1500 {
+                recorded.pause();
+                recorded.tick_timers();
+                recorded.resume();
+            }
+            let key = [0x2, 0x4, 0x6, 0x8][cycle / 400 % 4];
+            recorded.set_keypad_state(if cycle % 200 < 100 { 1 << key } else { 0 });
+            recorded.run_once().unwrap();
+        }
+        recorded.execute(crate::Chip8Instr::Rand(0, 0xFF)).unwrap();
+        recorded.stop_recording();
+        let file = std::fs::read_to_string(&path).unwrap();
+        assert!(file.starts_with("chip8-replay 1\nseed "));
+        assert!(file.ends_with("3000 END\n"));
+        assert!(file.contains("\n1500 F\n"));
+        assert!(file.contains(" K 0004\n"));
+
+        let mut replayed = vm();
+        replayed.replay_from(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        replayed.execute(crate::Chip8Instr::Rand(0, 0xFF)).unwrap();
+        assert_eq!(replayed.cycles(), 3000);
+        assert_eq!(replayed.to_state(), recorded.to_state());
+    }
+
+    #[test]
+    fn invalid_files() {
+        let path =
+            std::env::temp_dir().join(format!("chip-8-replay-bad-{}.txt", std::process::id()));
+        for (content, line) in [
+            ("chip8-replay 2\nseed 1\n", None),
+            ("chip8-replay 1\nseed x\n", Some(2)),
+            ("chip8-replay 1\nseed 1\n0 K 0000\n5 X\n", Some(4)),
+        ] {
+            std::fs::write(&path, content).unwrap();
+            match (vm().replay_from(&path), line) {
+                (Err(ReplayError::Header), None) => {}
+                (Err(ReplayError::Line(l)), Some(line)) => assert_eq!(l, line),
+                (result, _) => panic!("{content:?}: {result:?}"),
+            }
+        }
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,326 @@
+use alloc::vec::Vec;
+
+use crate::{Chip8Error, Chip8VM, Chip8VMOptions, Keypad, Registers};
+
+// Plain copy of everything that makes up a running VM, minus the timer thread.
+// RAM and display are stored as vectors so the serde representation stays simple.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct VmState {
+    pub ram: Vec<u8>,
+    // One row of DISPLAY_WIDTH pixels per entry
+    pub display: Vec<Vec<bool>>,
+    // Second XO-CHIP plane, same layout (blank when empty, as in older states)
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub plane2: Vec<Vec<bool>>,
+    // Planes selected for drawing, see `Chip8VM::select_planes`
+    #[cfg_attr(feature = "serde", serde(default = "first_plane"))]
+    pub planes: u8,
+    pub pc: u16,
+    pub i: u16,
+    pub v: [u8; 16],
+    pub stack: Vec<u16>,
+    pub delay: u8,
+    pub buzzer: u8,
+    pub keypad: u16,
+    // Key held during a pending GetKey
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub key_wait: Option<u8>,
+    pub freq: u32,
+    pub options: Chip8VMOptions,
+}
+
+// Only the first plane in states saved before the XO-CHIP planes
+#[cfg(feature = "serde")]
+fn first_plane() -> u8 {
+    1
+}
+
+// Registers to set with `Chip8VM::with_registers`, the others are kept. Lighter than a
+// whole `VmState` to reproduce a bug, e.g. "V5=7 and I=0x2EA":
+// `RegisterPatch { v: vec![(5, 7)], i: Some(0x2EA), ..Default::default() }`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RegisterPatch {
+    // (register number, value)
+    pub v: Vec<(u8, u8)>,
+    pub i: Option<u16>,
+    pub pc: Option<u16>,
+}
+
+impl Chip8VM {
+    // Applies `patch`, or nothing if a register number is above 0xF, PC is odd or an
+    // address is outside of the RAM
+    pub fn with_registers(&mut self, patch: &RegisterPatch) -> Result<(), Chip8Error> {
+        if patch.v.iter().any(|&(reg, _)| reg > 0xF) {
+            return Err(Chip8Error::InvalidState("register number above 0xF"));
+        }
+        if let Some(pc) = patch.pc {
+            if pc % 2 != 0 {
+                return Err(Chip8Error::InvalidState("odd PC"));
+            }
+        }
+        for addr in [patch.pc, patch.i].into_iter().flatten() {
+            if addr as usize >= Self::RAM_SIZE {
+                return Err(Chip8Error::OutOfBounds(addr as usize));
+            }
+        }
+
+        for &(reg, value) in &patch.v {
+            self.registers.set(reg, value);
+        }
+        if let Some(i) = patch.i {
+            self.registers.i = i;
+        }
+        if let Some(pc) = patch.pc {
+            self.registers.pc = pc;
+            self.halted = false;
+        }
+        Ok(())
+    }
+
+    pub fn to_state(&self) -> VmState {
+        let timers = self.timers.get();
+        VmState {
+            ram: self.ram.to_vec(),
+            display: self.display.iter().map(|row| row.to_vec()).collect(),
+            plane2: self.plane2.iter().map(|row| row.to_vec()).collect(),
+            planes: self.planes,
+            pc: self.registers.pc,
+            i: self.registers.i,
+            v: core::array::from_fn(|r| self.registers.get(r as u8)),
+            stack: self.stack.clone(),
+            delay: timers.delay,
+            buzzer: timers.buzzer,
+            keypad: self.keypad.0,
+            key_wait: self.key_wait,
+            freq: self.freq,
+            options: self.options.clone(),
+        }
+    }
+
+    // Copies the machine state of this VM into `other`, like `load_state` without going
+    // through a `VmState`: RAM, registers, stack, display, timers and keypad. The options,
+    // clock speed and timer thread of `other` are kept, e.g. to step a reference and a
+    // subject VM side by side from the same point.
+    pub fn clone_state_into(&self, other: &mut Chip8VM) {
+        other.ram = self.ram;
+        other.ram_written(0, Self::RAM_SIZE);
+        other.display = self.display;
+        other.plane2 = self.plane2;
+        other.planes = self.planes;
+        other.display_changed = true;
+        other.registers = self.registers;
+        other.stack.clone_from(&self.stack);
+        let timers = self.timers.get();
+        other.timers.modify(|t| *t = timers);
+        other.keypad = self.keypad;
+        other.key_wait = self.key_wait;
+        other.halted = self.halted;
+        other.history.clear();
+    }
+
+    // Builds a new VM (with its own timers, as selected by the state's options)
+    pub fn from_state(state: &VmState) -> Result<Self, Chip8Error> {
+        let mut vm = Chip8VM::new(Some(state.freq), None, Some(state.options.clone()));
+        vm.load_state(state)?;
+        Ok(vm)
+    }
+
+    // Overwrites the machine state, the options and timer mode of this VM are kept
+    pub fn load_state(&mut self, state: &VmState) -> Result<(), Chip8Error> {
+        let ram = state
+            .ram
+            .as_slice()
+            .try_into()
+            .map_err(|_| Chip8Error::InvalidState("RAM size"))?;
+        let display_size_ok = |display: &Vec<Vec<bool>>| {
+            display.len() == Self::DISPLAY_HEIGHT
+                && display.iter().all(|r| r.len() == Self::DISPLAY_WIDTH)
+        };
+        if !display_size_ok(&state.display)
+            || !(state.plane2.is_empty() || display_size_ok(&state.plane2))
+        {
+            return Err(Chip8Error::InvalidState("display size"));
+        }
+        if state.pc as usize >= Self::RAM_SIZE || state.i as usize >= Self::RAM_SIZE {
+            return Err(Chip8Error::InvalidState("address register out of RAM"));
+        }
+
+        self.ram = ram;
+        self.ram_written(0, Self::RAM_SIZE);
+        for (y, row) in state.display.iter().enumerate() {
+            self.display[y].copy_from_slice(row);
+        }
+        self.plane2 = [[false; Self::DISPLAY_WIDTH]; Self::DISPLAY_HEIGHT];
+        for (y, row) in state.plane2.iter().enumerate() {
+            self.plane2[y].copy_from_slice(row);
+        }
+        self.planes = state.planes & 0b11;
+        self.display_changed = true;
+        self.halted = false;
+        self.registers = Registers {
+            pc: state.pc,
+            i: state.i,
+            ..Registers::default()
+        };
+        for (r, v) in state.v.iter().enumerate() {
+            self.registers.set(r as u8, *v);
+        }
+        self.stack = state.stack.clone();
+        self.timers.modify(|t| {
+            t.delay = state.delay;
+            t.buzzer = state.buzzer;
+        });
+        self.keypad = Keypad(state.keypad);
+        self.key_wait = state.key_wait.filter(|&k| k < 16);
+        self.freq = state.freq;
+        self.history.clear();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::test_vm;
+    use crate::Chip8Instr;
+    use alloc::vec;
+
+    fn sample_vm() -> Chip8VM {
+        let mut vm = Chip8VM::new(
+            Some(500),
+            None,
+            Some(Chip8VMOptions {
+                manual_timers: true,
+                old_shift: true,
+                ..Default::default()
+            }),
+        );
+        vm.load_rom(&[0x12, 0x34, 0xAB, 0xCD]);
+        vm.registers.set(0xA, 0x42);
+        vm.registers.i = 0x2F0;
+        vm.stack.push(0x204);
+        vm.display[3][7] = true;
+        vm.plane2[4][8] = true;
+        vm.select_planes(0b10);
+        vm.registers.set(0, 9);
+        vm.execute(Chip8Instr::SetDelay(0)).unwrap();
+        vm.keypad.press(5);
+        vm
+    }
+
+    #[test]
+    fn state_round_trip() {
+        let vm = sample_vm();
+        let state = vm.to_state();
+        let copy = Chip8VM::from_state(&state).unwrap();
+        assert_eq!(copy.to_state(), state);
+        assert_eq!(copy.ram, vm.ram);
+        assert_eq!(copy.display, vm.display);
+        assert_eq!(copy.plane2, vm.plane2);
+        assert_eq!(copy.selected_planes(), 0b10);
+        assert_eq!(copy.registers.get(0xA), 0x42);
+        assert_eq!(copy.timers.get().delay, 9);
+    }
+
+    #[test]
+    fn clone_into_other_vm() {
+        let vm = sample_vm();
+        let mut other = test_vm();
+        vm.clone_state_into(&mut other);
+        assert_eq!(other.display, vm.display);
+        assert_eq!(other.plane(1), vm.plane(1));
+        assert_eq!(other.selected_planes(), 0b10);
+        assert_eq!(other.ram, vm.ram);
+        assert_eq!(other.stack, vm.stack);
+        assert_eq!(other.timers.get().delay, 9);
+        assert!(!other.options.old_shift);
+        assert_eq!(other.freq, Chip8VM::FREQ);
+        // Same states apart from the options and clock speed
+        let state = other.to_state();
+        assert_eq!(
+            state,
+            VmState {
+                freq: state.freq,
+                options: state.options.clone(),
+                ..vm.to_state()
+            }
+        );
+    }
+
+    #[test]
+    fn state_invalid() {
+        let mut state = sample_vm().to_state();
+        state.ram.pop();
+        assert!(Chip8VM::from_state(&state).is_err());
+
+        let mut state = sample_vm().to_state();
+        state.display[0].push(true);
+        assert!(Chip8VM::from_state(&state).is_err());
+
+        let mut state = sample_vm().to_state();
+        state.plane2.pop();
+        assert!(Chip8VM::from_state(&state).is_err());
+    }
+
+    #[test]
+    fn register_patch() {
+        let mut vm = sample_vm();
+        vm.with_registers(&RegisterPatch {
+            v: vec![(5, 7), (0xF, 1)],
+            i: Some(0x2EA),
+            ..Default::default()
+        })
+        .unwrap();
+        assert_eq!(vm.registers.get(5), 7);
+        assert_eq!(vm.registers.get(0xF), 1);
+        assert_eq!(vm.registers.get(0xA), 0x42);
+        assert_eq!(vm.registers.i, 0x2EA);
+        assert_eq!(vm.registers.pc, 0x200);
+
+        let invalid = [
+            RegisterPatch {
+                v: vec![(1, 1), (16, 1)],
+                ..Default::default()
+            },
+            RegisterPatch {
+                pc: Some(0x201),
+                ..Default::default()
+            },
+            RegisterPatch {
+                i: Some(0x1000),
+                ..Default::default()
+            },
+        ];
+        let before = vm.to_state();
+        for patch in &invalid {
+            assert!(vm.with_registers(patch).is_err(), "{patch:?}");
+        }
+        assert_eq!(vm.to_state(), before);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn state_json_round_trip() {
+        let state = sample_vm().to_state();
+        let json = serde_json::to_string(&state).unwrap();
+        let parsed: VmState = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed, state);
+        assert_eq!(Chip8VM::from_state(&parsed).unwrap().to_state(), state);
+
+        // Saved before the planes: blank second plane, only the first one selected
+        let mut value = serde_json::to_value(&state).unwrap();
+        let object = value.as_object_mut().unwrap();
+        object.remove("plane2");
+        object.remove("planes");
+        // Options added since then are missing too
+        let options = object["options"].as_object_mut().unwrap();
+        options.remove("align_pc");
+        options.remove("clip_sprite_start");
+        let old: VmState = serde_json::from_value(value).unwrap();
+        let vm = Chip8VM::from_state(&old).unwrap();
+        assert!(vm.plane2.iter().flatten().all(|&on| !on));
+        assert_eq!(vm.selected_planes(), 1);
+        assert_eq!(vm.options, state.options);
+    }
+}
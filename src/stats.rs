@@ -0,0 +1,80 @@
+use std::fmt;
+
+use crate::Chip8VM;
+
+// Where a ROM spent its time, see `Chip8VM::run_stats`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RunStats {
+    // Instructions executed while the stats were recorded
+    pub instructions: u64,
+    // Executions per instruction kind, most executed first
+    pub histogram: Vec<(&'static str, u64)>,
+    // Most executed addresses first
+    pub hot_addresses: Vec<(u16, u64)>,
+}
+
+impl Chip8VM {
+    // Instruction histogram and the `top` most executed addresses, only recorded with the
+    // `opcode_stats` option (empty otherwise). Ties are in name and address order.
+    pub fn run_stats(&self, top: usize) -> RunStats {
+        let mut histogram: Vec<_> = self.opcode_counts.iter().map(|(&k, &v)| (k, v)).collect();
+        histogram.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+        let mut hot_addresses: Vec<_> = self.pc_counts.iter().map(|(&k, &v)| (k, v)).collect();
+        hot_addresses.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        hot_addresses.truncate(top);
+        RunStats {
+            instructions: histogram.iter().map(|(_, count)| count).sum(),
+            histogram,
+            hot_addresses,
+        }
+    }
+}
+
+// A table per section, with the share of the executed instructions
+impl fmt::Display for RunStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let share = |count: u64| 100. * count as f64 / self.instructions.max(1) as f64;
+        writeln!(f, "{} instructions", self.instructions)?;
+        for (name, count) in &self.histogram {
+            writeln!(f, "  {name:<12} {count:>10} {:>6.2}%", share(*count))?;
+        }
+        writeln!(f, "hottest addresses")?;
+        for (addr, count) in &self.hot_addresses {
+            writeln!(
+                f,
+                "  {addr:#05x}        {count:>10} {:>6.2}%",
+                share(*count)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tests::test_vm;
+
+    #[test]
+    fn histogram_and_hot_addresses() {
+        let mut vm = test_vm();
+        vm.options.opcode_stats = true;
+        // LD V0, 3 ; loop: ADD V0, -1 ; SE V0, 0 ; JP loop ; CLS
+        vm.load_rom(&[0x60, 0x03, 0x70, 0xFF, 0x30, 0x00, 0x12, 0x02, 0x00, 0xE0]);
+        vm.step(9);
+        let stats = vm.run_stats(2);
+        assert_eq!(stats.instructions, 9);
+        assert_eq!(
+            stats.histogram,
+            [("Add", 3), ("SkipEqImm", 3), ("Jump", 2), ("Set", 1)]
+        );
+        assert_eq!(stats.hot_addresses, [(0x202, 3), (0x204, 3)]);
+        let table = stats.to_string();
+        assert!(table.starts_with("9 instructions\n  Add"), "{table}");
+        assert!(table.contains("  0x202"), "{table}");
+
+        let mut vm = test_vm();
+        vm.load_rom(&[0x60, 0x03]);
+        vm.run_once().unwrap();
+        assert_eq!(vm.run_stats(10).instructions, 0);
+    }
+}
@@ -0,0 +1,324 @@
+use std::fmt::Write as _;
+use std::io::{IsTerminal, Read};
+use std::process::{Command, Stdio};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::{Display, Intensity, Keypad, Renderer};
+
+// Characters of the frame drawn around the display, see `TerminalRenderer::with_border`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TerminalBorder {
+    pub horizontal: char,
+    pub vertical: char,
+    // Top left, top right, bottom left and bottom right
+    pub corners: [char; 4],
+}
+impl Default for TerminalBorder {
+    fn default() -> Self {
+        TerminalBorder {
+            horizontal: '─',
+            vertical: '│',
+            corners: ['┌', '┐', '└', '┘'],
+        }
+    }
+}
+
+// Draws the display with one emoji square per pixel
+pub struct TerminalRenderer {
+    // Print frames one after the other instead of clearing the terminal
+    pub keep_display: bool,
+    // The screen holds a full frame, which can be updated row by row
+    drawn: bool,
+    border: Option<TerminalBorder>,
+    // Line above the display with the VM state, see `Renderer::set_status`
+    show_status: bool,
+    status: String,
+}
+impl TerminalRenderer {
+    pub fn new(keep_display: bool) -> Self {
+        TerminalRenderer {
+            keep_display,
+            drawn: false,
+            border: None,
+            show_status: false,
+            status: String::new(),
+        }
+    }
+
+    // Frames the display, so it stands out from the rest of the terminal
+    pub fn with_border(mut self, border: TerminalBorder) -> Self {
+        self.border = Some(border);
+        self
+    }
+
+    // Prints PC and the cycle count above the display
+    pub fn with_status_line(mut self) -> Self {
+        self.show_status = true;
+        self
+    }
+
+    // Terminal lines above the first row of pixels
+    fn top_lines(&self) -> usize {
+        self.show_status as usize + self.border.is_some() as usize
+    }
+
+    fn push_row(&self, out: &mut String, row: &[bool]) {
+        if let Some(border) = &self.border {
+            out.push(border.vertical);
+        }
+        for &pixel in row {
+            out.push(if pixel { '⬜' } else { '⬛' });
+        }
+        if let Some(border) = &self.border {
+            out.push(border.vertical);
+        }
+    }
+
+    // Top or bottom of the border, pixels are 2 columns wide
+    fn push_edge(out: &mut String, border: &TerminalBorder, width: usize, top: bool) {
+        let [top_left, top_right, bottom_left, bottom_right] = border.corners;
+        out.push(if top { top_left } else { bottom_left });
+        out.extend(core::iter::repeat_n(border.horizontal, 2 * width));
+        out.push(if top { top_right } else { bottom_right });
+        out.push('\n');
+    }
+
+    // Status line, border and pixels
+    fn full_frame(&self, display: &Display) -> String {
+        let mut out = String::new();
+        if self.show_status {
+            out.push_str(&self.status);
+            out.push('\n');
+        }
+        let width = display[0].len();
+        if let Some(border) = &self.border {
+            Self::push_edge(&mut out, border, width, true);
+        }
+        for row in display {
+            self.push_row(&mut out, row);
+            out.push('\n');
+        }
+        if let Some(border) = &self.border {
+            Self::push_edge(&mut out, border, width, false);
+        }
+        out
+    }
+}
+impl Renderer for TerminalRenderer {
+    fn render(&mut self, display: &Display) {
+        let clear = if self.keep_display { "" } else { "\x1bc" };
+        print!("{clear}{}", self.full_frame(display));
+        self.drawn = true;
+    }
+
+    fn set_status(&mut self, status: &str) {
+        self.status.clear();
+        self.status.push_str(status);
+    }
+
+    // Printing is slow, only the changed rows are printed over the previous frame
+    fn render_rows(&mut self, display: &Display, _intensity: &Intensity, dirty_rows: u32) {
+        if self.keep_display || !self.drawn {
+            return self.render(display);
+        }
+        let mut out = String::new();
+        if self.show_status {
+            // Rewritten in place, clearing the rest of the previous one
+            let _ = write!(out, "\x1b[1;1H{}\x1b[K", self.status);
+        }
+        let top = self.top_lines();
+        for (y, row) in display.iter().enumerate() {
+            if dirty_rows & (1 << y) != 0 {
+                // Cursor to the start of the row, rows and columns start at 1
+                let _ = write!(out, "\x1b[{};1H", top + y + 1);
+                self.push_row(&mut out, row);
+            }
+        }
+        // Back below the display
+        let bottom = top + display.len() + self.border.is_some() as usize;
+        let _ = write!(out, "\x1b[{};1H", bottom + 1);
+        print!("{out}");
+    }
+}
+
+// Maps the characters typed in the terminal to CHIP-8 keys (0-9, a-f)
+pub fn key_from_char(c: char) -> Option<u8> {
+    match c {
+        k if k.is_ascii_digit() => Some(k as u8 - b'0'),
+        k @ 'a'..='f' => Some(k as u8 - b'a' + 10),
+        k @ 'A'..='F' => Some(k as u8 - b'A' + 10),
+        _ => None,
+    }
+}
+
+// Keypad input read from stdin.
+// While it exists, a terminal stdin is switched to non-canonical mode without echo
+// (with `stty`, so only on Unix-like systems), so keys are read as they are typed
+// instead of after Enter. The previous mode is restored when it is dropped.
+// Terminals only report key presses (repeated while the key is held) and no releases,
+// so a key stays down as long as it keeps repeating and is released once no event
+// was seen for `hold_timeout`. Repeats of a held key are not reported as new presses.
+pub struct TerminalInput {
+    events: Receiver<u8>,
+    last_seen: [Option<Instant>; 16],
+    hold_timeout: Duration,
+    // Only held for its drop
+    _raw_mode: Option<RawMode>,
+}
+impl TerminalInput {
+    // Longer than the usual delay before the OS starts repeating a held key
+    pub const DEFAULT_HOLD_TIMEOUT: Duration = Duration::from_millis(500);
+
+    // Spawns a thread reading stdin, it exits at the first byte read after `self` is dropped
+    pub fn new(hold_timeout: Duration) -> Self {
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            for byte in std::io::stdin().lock().bytes() {
+                let Ok(byte) = byte else { return };
+                if let Some(key) = key_from_char(byte as char) {
+                    if tx.send(key).is_err() {
+                        return;
+                    }
+                }
+            }
+        });
+        TerminalInput {
+            _raw_mode: RawMode::enable(),
+            ..Self::from_receiver(rx, hold_timeout)
+        }
+    }
+
+    fn from_receiver(events: Receiver<u8>, hold_timeout: Duration) -> Self {
+        TerminalInput {
+            events,
+            last_seen: [None; 16],
+            hold_timeout,
+            _raw_mode: None,
+        }
+    }
+
+    // Applies the pending terminal events to `keypad`, returns the keys newly pressed
+    pub fn poll(&mut self, keypad: &mut Keypad) -> Keypad {
+        let mut pressed = Keypad::default();
+        let now = Instant::now();
+        self.release_expired(keypad, now);
+        while let Ok(key) = self.events.try_recv() {
+            if self.register(keypad, key, now) {
+                pressed.press(key);
+            }
+        }
+        pressed
+    }
+
+    fn release_expired(&mut self, keypad: &mut Keypad, now: Instant) {
+        for (key, seen) in self.last_seen.iter_mut().enumerate() {
+            if seen.is_some_and(|t| now - t > self.hold_timeout) {
+                *seen = None;
+                keypad.release(key as u8);
+            }
+        }
+    }
+
+    // Whether the event is a new press rather than a repeat
+    fn register(&mut self, keypad: &mut Keypad, key: u8, now: Instant) -> bool {
+        keypad.press(key);
+        self.last_seen[key as usize].replace(now).is_none()
+    }
+}
+
+// Terminal settings saved by `stty -g`, put back on drop
+struct RawMode {
+    saved: String,
+}
+impl RawMode {
+    // None when stdin is not a terminal or `stty` failed, keys then arrive line by line
+    fn enable() -> Option<Self> {
+        if !std::io::stdin().is_terminal() {
+            return None;
+        }
+        let saved = stty(&["-g"])?;
+        stty(&["-icanon", "-echo", "min", "1"])?;
+        Some(RawMode {
+            saved: saved.trim().to_string(),
+        })
+    }
+}
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        stty(&[&self.saved]);
+    }
+}
+
+// `stty` acts on the terminal of its stdin, so it inherits ours
+fn stty(args: &[&str]) -> Option<String> {
+    let output = Command::new("stty")
+        .args(args)
+        .stdin(Stdio::inherit())
+        .output()
+        .ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hold_and_release() {
+        let (tx, rx) = mpsc::channel();
+        let mut input = TerminalInput::from_receiver(rx, Duration::from_millis(50));
+        let mut keypad = Keypad::default();
+
+        tx.send(5).unwrap();
+        assert!(input.poll(&mut keypad).is_pressed(5));
+        assert!(keypad.is_pressed(5));
+
+        // Key repeat: still down but not a new press
+        tx.send(5).unwrap();
+        assert_eq!(input.poll(&mut keypad), Keypad::default());
+        assert!(keypad.is_pressed(5));
+
+        thread::sleep(Duration::from_millis(80));
+        input.poll(&mut keypad);
+        assert!(!keypad.is_pressed(5));
+    }
+
+    #[test]
+    fn border_and_status() {
+        let mut display = [[false; 64]; 32];
+        display[0][0] = true;
+        let mut renderer = TerminalRenderer::new(true)
+            .with_border(TerminalBorder {
+                horizontal: '-',
+                vertical: '|',
+                corners: ['+'; 4],
+            })
+            .with_status_line();
+        renderer.set_status("PC=0x200 cycles=0");
+        let frame = renderer.full_frame(&display);
+        let lines: Vec<&str> = frame.lines().collect();
+        assert_eq!(lines.len(), 1 + 1 + 32 + 1);
+        assert_eq!(lines[0], "PC=0x200 cycles=0");
+        assert_eq!(lines[1], format!("+{}+", "-".repeat(128)));
+        assert!(lines[2].starts_with("|⬜⬛") && lines[2].ends_with("⬛|"));
+        assert_eq!(lines[34], lines[1]);
+
+        // Only the pixels without the options
+        let plain = TerminalRenderer::new(true).full_frame(&display);
+        assert_eq!(plain.lines().count(), 32);
+        assert!(plain.starts_with("⬜⬛"));
+    }
+
+    #[test]
+    fn char_mapping() {
+        assert_eq!(key_from_char('7'), Some(7));
+        assert_eq!(key_from_char('c'), Some(0xC));
+        assert_eq!(key_from_char('F'), Some(0xF));
+        assert_eq!(key_from_char('g'), None);
+    }
+}
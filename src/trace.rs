@@ -0,0 +1,83 @@
+use std::fmt::Write as _;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use crate::Chip8VM;
+
+// Where the execution trace is written, see `Chip8VM::set_trace`
+pub type TraceSink = Box<dyn Write + Send>;
+
+impl Chip8VM {
+    // Writes one line per executed instruction to `out`, with the state before it runs:
+    //
+    //   PC:0200 OP:00E0 I:0000 V:00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 00
+    //
+    // Fixed width upper case hex, V0 to VF in order, so traces of different emulators
+    // can be compared with `diff`. This format won't change.
+    // The trace is dropped (and stops) at the first write error.
+    pub fn set_trace(&mut self, out: TraceSink) {
+        self.trace = Some(out);
+    }
+
+    // `set_trace` to a new file at `path`
+    pub fn trace_to_file(&mut self, path: impl AsRef<Path>) -> io::Result<()> {
+        let file = File::create(path)?;
+        self.set_trace(Box::new(BufWriter::new(file)));
+        Ok(())
+    }
+
+    // Stops tracing and flushes what was written
+    pub fn stop_trace(&mut self) -> io::Result<()> {
+        match self.trace.take() {
+            Some(mut out) => out.flush(),
+            None => Ok(()),
+        }
+    }
+
+    pub(crate) fn write_trace(&mut self, opcode: u16) {
+        let Some(out) = &mut self.trace else {
+            return;
+        };
+        let mut line = format!(
+            "PC:{:04X} OP:{opcode:04X} I:{:04X} V:",
+            self.registers.pc, self.registers.i
+        );
+        for reg in 0..16 {
+            let sep = if reg == 0 { "" } else { " " };
+            let _ = write!(line, "{sep}{:02X}", self.registers.get(reg));
+        }
+        line.push('\n');
+        if out.write_all(line.as_bytes()).is_err() {
+            self.trace = None;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tests::test_vm;
+
+    #[test]
+    fn trace_file() {
+        let path = std::env::temp_dir().join(format!("chip-8-trace-{}.log", std::process::id()));
+        let mut vm = test_vm();
+        // LD V0, 0x12 ; LD VF, 0xAB ; LD I, 0x345
+        vm.load_rom(&[0x60, 0x12, 0x6F, 0xAB, 0xA3, 0x45]);
+        vm.trace_to_file(&path).unwrap();
+        for _ in 0..3 {
+            vm.run_once().unwrap();
+        }
+        vm.stop_trace().unwrap();
+        vm.run_once().unwrap();
+
+        let trace = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(
+            trace,
+            "PC:0200 OP:6012 I:0000 V:00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 00\n\
+             PC:0202 OP:6FAB I:0000 V:12 00 00 00 00 00 00 00 00 00 00 00 00 00 00 00\n\
+             PC:0204 OP:A345 I:0000 V:12 00 00 00 00 00 00 00 00 00 00 00 00 00 00 AB\n"
+        );
+    }
+}
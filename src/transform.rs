@@ -0,0 +1,181 @@
+use alloc::vec::Vec;
+
+use crate::{Chip8VM, Display, Intensity, Renderer};
+
+const WIDTH: usize = Chip8VM::DISPLAY_WIDTH;
+const HEIGHT: usize = Chip8VM::DISPLAY_HEIGHT;
+
+// Orientation of a physical panel relative to the CHIP-8 screen, rotations are clockwise
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum DisplayTransform {
+    #[default]
+    Identity,
+    Rotate90,
+    Rotate180,
+    Rotate270,
+    // Mirrored left to right
+    FlipHorizontal,
+    // Mirrored top to bottom
+    FlipVertical,
+}
+
+impl DisplayTransform {
+    // Width and height of the transformed image, 32x64 when rotated by a quarter turn
+    pub fn size(self) -> (usize, usize) {
+        match self {
+            Self::Rotate90 | Self::Rotate270 => (HEIGHT, WIDTH),
+            _ => (WIDTH, HEIGHT),
+        }
+    }
+
+    // Where the screen pixel (x, y) ends up in the transformed image
+    pub fn map(self, x: usize, y: usize) -> (usize, usize) {
+        match self {
+            Self::Identity => (x, y),
+            Self::Rotate90 => (HEIGHT - 1 - y, x),
+            Self::Rotate180 => (WIDTH - 1 - x, HEIGHT - 1 - y),
+            Self::Rotate270 => (y, WIDTH - 1 - x),
+            Self::FlipHorizontal => (WIDTH - 1 - x, y),
+            Self::FlipVertical => (x, HEIGHT - 1 - y),
+        }
+    }
+
+    // The transformed image in `out`, row by row (`size().0` pixels per row)
+    pub fn apply(self, intensity: &Intensity, out: &mut Vec<u8>) {
+        let (width, height) = self.size();
+        out.clear();
+        out.resize(width * height, 0);
+        for (y, row) in intensity.iter().enumerate() {
+            for (x, &level) in row.iter().enumerate() {
+                let (tx, ty) = self.map(x, y);
+                out[ty * width + tx] = level;
+            }
+        }
+    }
+}
+
+// Output for panels whose size depends on the transform, see `TransformRenderer`
+pub trait PanelRenderer {
+    // `pixels` has `width * height` levels row by row, 255 for a lit pixel
+    fn render_panel(&mut self, width: usize, height: usize, pixels: &[u8]);
+}
+
+// Renderer applying a `DisplayTransform` before handing the frame to a panel.
+// Only the output is transformed, the VM display (and so collisions) is unaffected.
+pub struct TransformRenderer<P> {
+    panel: P,
+    transform: DisplayTransform,
+    buffer: Vec<u8>,
+}
+
+impl<P: PanelRenderer> TransformRenderer<P> {
+    pub fn new(panel: P, transform: DisplayTransform) -> Self {
+        TransformRenderer {
+            panel,
+            transform,
+            buffer: Vec::new(),
+        }
+    }
+
+    pub fn set_transform(&mut self, transform: DisplayTransform) {
+        self.transform = transform;
+    }
+
+    pub fn panel(&self) -> &P {
+        &self.panel
+    }
+}
+
+impl<P: PanelRenderer> Renderer for TransformRenderer<P> {
+    fn render(&mut self, display: &Display) {
+        let intensity = display.map(|row| row.map(|on| if on { 255 } else { 0 }));
+        self.render_intensity(display, &intensity);
+    }
+
+    fn render_intensity(&mut self, _display: &Display, intensity: &Intensity) {
+        self.transform.apply(intensity, &mut self.buffer);
+        let (width, height) = self.transform.size();
+        self.panel.render_panel(width, height, &self.buffer);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Panel {
+        width: usize,
+        height: usize,
+        pixels: Vec<u8>,
+    }
+    impl PanelRenderer for Panel {
+        fn render_panel(&mut self, width: usize, height: usize, pixels: &[u8]) {
+            self.width = width;
+            self.height = height;
+            self.pixels = pixels.to_vec();
+        }
+    }
+    impl Panel {
+        fn lit(&self) -> Vec<(usize, usize)> {
+            let mut lit = Vec::new();
+            for (n, &level) in self.pixels.iter().enumerate() {
+                if level != 0 {
+                    lit.push((n % self.width, n / self.width));
+                }
+            }
+            lit
+        }
+    }
+
+    #[test]
+    fn transform_pattern() {
+        // An L in the top left corner: (0, 0), (0, 1) and (1, 1)
+        let mut display = [[false; WIDTH]; HEIGHT];
+        display[0][0] = true;
+        display[1][0] = true;
+        display[1][1] = true;
+        for (transform, size, lit) in [
+            (
+                DisplayTransform::Identity,
+                (64, 32),
+                [(0, 0), (0, 1), (1, 1)],
+            ),
+            (
+                DisplayTransform::Rotate90,
+                (32, 64),
+                [(30, 0), (31, 0), (30, 1)],
+            ),
+            (
+                DisplayTransform::Rotate180,
+                (64, 32),
+                [(62, 30), (63, 30), (63, 31)],
+            ),
+            (
+                DisplayTransform::Rotate270,
+                (32, 64),
+                [(1, 62), (0, 63), (1, 63)],
+            ),
+            (
+                DisplayTransform::FlipHorizontal,
+                (64, 32),
+                [(63, 0), (62, 1), (63, 1)],
+            ),
+            (
+                DisplayTransform::FlipVertical,
+                (64, 32),
+                [(0, 30), (1, 30), (0, 31)],
+            ),
+        ] {
+            let panel = Panel {
+                width: 0,
+                height: 0,
+                pixels: Vec::new(),
+            };
+            let mut renderer = TransformRenderer::new(panel, transform);
+            renderer.render(&display);
+            let panel = renderer.panel();
+            assert_eq!((panel.width, panel.height), size, "{transform:?}");
+            assert_eq!(panel.lit(), lit, "{transform:?}");
+        }
+    }
+}
@@ -0,0 +1,82 @@
+use wasm_bindgen::prelude::*;
+
+use crate::{Chip8VM, Chip8VMOptions};
+
+// Browser facing wrapper, the page is responsible for calling `step` at the CPU
+// frequency, `tick_timers` at 60Hz and drawing the display (see examples/wasm).
+#[wasm_bindgen]
+pub struct WasmChip8 {
+    vm: Chip8VM,
+}
+
+#[wasm_bindgen]
+impl WasmChip8 {
+    #[wasm_bindgen(constructor)]
+    pub fn new(freq: u32) -> WasmChip8 {
+        WasmChip8 {
+            vm: Chip8VM::new(
+                Some(freq),
+                None,
+                Some(Chip8VMOptions {
+                    hide_display: true,
+                    manual_timers: true,
+                    ..Default::default()
+                }),
+            ),
+        }
+    }
+
+    pub fn freq(&self) -> u32 {
+        self.vm.freq()
+    }
+
+    // Fails with the error message when the ROM doesn't fit in the RAM
+    pub fn load_rom(&mut self, rom: &[u8]) -> Result<(), String> {
+        self.vm
+            .try_load_rom(rom)
+            .map(|_| ())
+            .map_err(|e| e.to_string())
+    }
+
+    // Fails with the error message when the instruction cannot be executed
+    pub fn step(&mut self) -> Result<(), String> {
+        self.vm.run_once().map_err(|e| e.to_string())
+    }
+
+    // Also the frame boundary where the display is presented
+    pub fn tick_timers(&mut self) {
+        self.vm.tick_timers();
+        self.vm.present();
+    }
+
+    pub fn display_width(&self) -> usize {
+        Chip8VM::DISPLAY_WIDTH
+    }
+
+    pub fn display_height(&self) -> usize {
+        Chip8VM::DISPLAY_HEIGHT
+    }
+
+    // Row-major pixels, one byte (0 or 1) per pixel, to be read from the wasm memory
+    pub fn display_ptr(&self) -> *const u8 {
+        self.vm.front_display().as_ptr() as *const u8
+    }
+
+    // Same layout as `display_ptr` but copied out
+    pub fn display_as_bytes(&self) -> Vec<u8> {
+        self.vm
+            .front_display()
+            .iter()
+            .flatten()
+            .map(|&p| p as u8)
+            .collect()
+    }
+
+    pub fn key_down(&mut self, key: u8) {
+        self.vm.keypad_mut().press(key);
+    }
+
+    pub fn key_up(&mut self, key: u8) {
+        self.vm.keypad_mut().release(key);
+    }
+}
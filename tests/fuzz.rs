@@ -0,0 +1,118 @@
+// Property test of the instruction handlers: random opcodes run for one cycle on random
+// machine states must never panic, only return errors.
+//
+// The cases come from a seeded rng so a failure is reproducible, its seed is printed.
+// Run more of them with `CHIP8_FUZZ_CASES=100000 cargo test --test fuzz` (not in release,
+// which would hide the arithmetic overflows).
+
+use std::panic::{catch_unwind, AssertUnwindSafe};
+
+use chip_8::{Chip8VM, Chip8VMOptions, VmState};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+const DEFAULT_CASES: u64 = 2_000;
+
+// Opcodes of real programs, mixed with the random ones
+const CORPUS: [&[u8]; 4] = [
+    include_bytes!("../ibm.ch8"),
+    include_bytes!("../test_opcode.ch8"),
+    include_bytes!("../bc_test.ch8"),
+    include_bytes!("../KALEID.ch8"),
+];
+
+fn random_state(rng: &mut StdRng, opcode: u16) -> VmState {
+    let mut ram = vec![0; Chip8VM::RAM_SIZE];
+    rng.fill(&mut ram[..]);
+    // Mostly even addresses, as reached by running a ROM
+    let pc = rng.gen_range(0..Chip8VM::RAM_SIZE) & if rng.gen_bool(0.9) { !1 } else { !0 };
+    ram[pc] = (opcode >> 8) as u8;
+    if pc + 1 < ram.len() {
+        ram[pc + 1] = opcode as u8;
+    }
+    let stack_len = rng.gen_range(0..=20);
+    let options = Chip8VMOptions {
+        hide_display: true,
+        manual_timers: true,
+        incr_i_when_mem: rng.gen(),
+        incr_i_past_last: rng.gen(),
+        new_jump_off: rng.gen(),
+        old_shift: rng.gen(),
+        vf_reset: rng.gen(),
+        wrap_addresses: rng.gen(),
+        display_wait: rng.gen(),
+        wrap_sprites: rng.gen(),
+        clip_sprite_start: rng.gen(),
+        protect_reserved: rng.gen(),
+        halt_on_self_jump: rng.gen(),
+        skip_unknown: rng.gen(),
+        strict: rng.gen(),
+        record_events: rng.gen(),
+        align_pc: rng.gen(),
+        ..Default::default()
+    };
+    VmState {
+        ram,
+        display: (0..Chip8VM::DISPLAY_HEIGHT)
+            .map(|_| (0..Chip8VM::DISPLAY_WIDTH).map(|_| rng.gen()).collect())
+            .collect(),
+        plane2: (0..Chip8VM::DISPLAY_HEIGHT)
+            .map(|_| (0..Chip8VM::DISPLAY_WIDTH).map(|_| rng.gen()).collect())
+            .collect(),
+        planes: rng.gen_range(0..4),
+        pc: pc as u16,
+        // Biased towards the end of the RAM, where the overflows are
+        i: if rng.gen() {
+            rng.gen_range(0..Chip8VM::RAM_SIZE as u16)
+        } else {
+            rng.gen_range(0xF00..Chip8VM::RAM_SIZE as u16)
+        },
+        v: rng.gen(),
+        stack: (0..stack_len)
+            .map(|_| rng.gen_range(0..Chip8VM::RAM_SIZE as u16))
+            .collect(),
+        delay: rng.gen(),
+        buzzer: rng.gen(),
+        keypad: rng.gen(),
+        key_wait: rng.gen_bool(0.2).then(|| rng.gen_range(0..16)),
+        freq: rng.gen_range(Chip8VM::MIN_FREQ..=Chip8VM::MAX_FREQ),
+        options,
+    }
+}
+
+#[test]
+fn opcodes_never_panic() {
+    let cases = std::env::var("CHIP8_FUZZ_CASES")
+        .ok()
+        .and_then(|cases| cases.parse().ok())
+        .unwrap_or(DEFAULT_CASES);
+    let corpus: Vec<u16> = CORPUS
+        .iter()
+        .flat_map(|rom| rom.chunks_exact(2))
+        .map(|op| u16::from_be_bytes([op[0], op[1]]))
+        .collect();
+
+    for seed in 0..cases {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let opcode = if rng.gen_bool(0.2) {
+            corpus[rng.gen_range(0..corpus.len())]
+        } else {
+            rng.gen()
+        };
+        let state = random_state(&mut rng, opcode);
+        let result = catch_unwind(AssertUnwindSafe(|| {
+            let mut vm = Chip8VM::from_state(&state).unwrap();
+            vm.set_rng(Box::new(StdRng::seed_from_u64(seed)));
+            let _ = vm.run_once();
+            // Nothing to check about the result, but reading the new state must not
+            // panic either
+            vm.to_state()
+        }));
+        assert!(
+            result.is_ok(),
+            "opcode {opcode:04X} panicked with seed {seed}, PC {:#05x}, I {:#05x}",
+            state.pc,
+            state.i
+        );
+    }
+}
@@ -0,0 +1,63 @@
+// Runs the test ROMs at the root of the repository headless and compares the final
+// display with golden hashes, see `chip_8::run_headless` and `chip_8::hash_display`.
+//
+// When a change legitimately alters what a ROM draws, check the new display first:
+//
+//   CHIP8_PRINT_GOLDENS=1 cargo test --test roms -- --nocapture
+//
+// prints each display and its hash, then update `GOLDENS` with the new values.
+
+use chip_8::{hash_display, run_headless, Display};
+
+// Enough instructions for each ROM to finish drawing its result screen
+const CYCLES: usize = 5_000;
+const SEED: u64 = 0;
+
+// ROM, name and hash of its display after `CYCLES` instructions.
+// The Timendus flags and quirks tests need a key press to pick the platform, they
+// belong here once the ROMs are added to the repository with a scripted keypad.
+const GOLDENS: [(&[u8], &str, u64); 4] = [
+    (include_bytes!("../ibm.ch8"), "ibm.ch8", 0x1f1d341cab07e169),
+    // corax89 opcode test, all "OK"
+    (
+        include_bytes!("../test_opcode.ch8"),
+        "test_opcode.ch8",
+        0x8f21671912c12851,
+    ),
+    // BestCoder test, "BON"
+    (
+        include_bytes!("../bc_test.ch8"),
+        "bc_test.ch8",
+        0x3f2181ca4969e69f,
+    ),
+    (
+        include_bytes!("../KALEID.ch8"),
+        "KALEID.ch8",
+        0x8113a6bed1bbffc1,
+    ),
+];
+
+fn print_display(display: &Display) {
+    for row in display {
+        let line: String = row.iter().map(|&on| if on { '#' } else { '.' }).collect();
+        println!("{line}");
+    }
+}
+
+#[test]
+fn rom_goldens() {
+    let print = std::env::var_os("CHIP8_PRINT_GOLDENS").is_some();
+    let mut mismatches = Vec::new();
+    for (rom, name, golden) in GOLDENS {
+        let display = run_headless(rom, CYCLES, SEED).unwrap();
+        let hash = hash_display(&display);
+        if print {
+            println!("{name}: {hash:#018x}");
+            print_display(&display);
+        }
+        if hash != golden {
+            mismatches.push(format!("{name}: expected {golden:#018x}, got {hash:#018x}"));
+        }
+    }
+    assert!(mismatches.is_empty(), "{}", mismatches.join("\n"));
+}